@@ -1,13 +1,34 @@
+use crate::{Image, LoadError, LoadSpec};
+
 #[derive(clap::Parser)]
 #[command(version, about, long_about = None)]
 pub struct Args {
-    pub file: String,
+    /// Program to load, written like the files of `--load`
+    #[arg(
+        required_unless_present = "load",
+        value_name = "FILE[@ADDR[,MIRROR...]]"
+    )]
+    pub file: Option<LoadSpec>,
+    /// Also load FILE, at ADDR for a raw binary or relocatable executable,
+    /// with a copy at each MIRROR address. Can be repeated.
+    #[arg(long, value_name = "FILE[@ADDR[,MIRROR...]]")]
+    pub load: Vec<LoadSpec>,
     #[arg(long = "log", short, default_value = "warn")]
     pub log_level: log::LevelFilter,
-    #[arg(long = "pc", short, default_value = "00")]
-    pub program_counter: String,
+    /// Start executing at this address instead of booting from the reset
+    /// vectors
+    #[arg(long = "pc", short)]
+    pub program_counter: Option<String>,
     #[arg(long = "usp", short)]
     pub user_stack_pointer: Option<String>,
     #[arg(long = "ssp", short)]
     pub system_stack_pointer: Option<String>,
 }
+
+impl Args {
+    /// Images of every file to load, in order, checked for overlaps
+    pub fn images(&self) -> Result<Vec<Image>, LoadError> {
+        let specs: Vec<_> = self.file.iter().chain(&self.load).cloned().collect();
+        LoadSpec::load_all(&specs)
+    }
+}
@@ -1,3 +1,4 @@
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Vector {
     ResetSP = 0x00,
     ResetPC = 0x04,
@@ -12,6 +13,7 @@ pub enum Vector {
     UnimplementedA = 0x28,
     UnimplementedF = 0x2C,
     Uninitialized = 0x3C,
+    Spurious = 0x60,
     Level1 = 0x64,
     Level2 = 0x68,
     Level3 = 0x6C,
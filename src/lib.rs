@@ -5,6 +5,12 @@ mod args;
 pub use args::Args;
 mod types;
 mod util;
-pub use vm::StatusRegister;
+pub use types::Size;
+pub use vm::{
+    AccessKind, BusError, CloneDevice, Device, FunctionCode, InterruptAck, Mmu, Ram, Rom,
+    StatusRegister, StepError, StopReason, Transaction,
+};
+mod loader;
+pub use loader::{Format, Image, LoadError, LoadSpec, Segment, SymbolTable};
 mod constants;
 pub use constants::*;
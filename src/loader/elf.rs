@@ -0,0 +1,201 @@
+use super::{Image, LoadError, ADDRESS_SPACE};
+
+pub(super) const MAGIC: [u8; 4] = [0x7F, b'E', b'L', b'F'];
+
+const ELFCLASS32: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+const EM_68K: u16 = 4;
+const PT_LOAD: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const STT_SECTION: u8 = 3;
+const STT_FILE: u8 = 4;
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16, LoadError> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or(LoadError::Invalid("truncated ELF file"))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, LoadError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(LoadError::Invalid("truncated ELF file"))
+}
+
+/// `len` bytes at `offset`
+fn slice(bytes: &[u8], offset: u32, len: u32) -> Result<&[u8], LoadError> {
+    bytes
+        .get(offset as usize..offset as usize + len as usize)
+        .ok_or(LoadError::Invalid("truncated ELF file"))
+}
+
+/// Parse an m68k ELF32 executable. PT_LOAD segments are placed at their
+/// physical address with the part past the file contents zero filled, and
+/// `e_entry` gives the entry point. Named symbols of `.symtab` are imported.
+pub(super) fn parse(bytes: &[u8]) -> Result<Image, LoadError> {
+    if !bytes.starts_with(&MAGIC) || bytes.len() < 52 {
+        return Err(LoadError::Invalid("not an ELF file"));
+    }
+    if bytes[4] != ELFCLASS32 || bytes[5] != ELFDATA2MSB {
+        return Err(LoadError::Invalid("not a 32 bit big endian ELF file"));
+    }
+    if u16_at(bytes, 18)? != EM_68K {
+        return Err(LoadError::Invalid("ELF file is not for the 68000"));
+    }
+    let mut image = Image {
+        entry: Some(u32_at(bytes, 24)?),
+        ..Default::default()
+    };
+
+    let phoff = u32_at(bytes, 28)? as usize;
+    let phentsize = u16_at(bytes, 42)? as usize;
+    for i in 0..u16_at(bytes, 44)? as usize {
+        let ph = phoff + i * phentsize;
+        if u32_at(bytes, ph)? != PT_LOAD {
+            continue;
+        }
+        let offset = u32_at(bytes, ph + 4)?;
+        let paddr = u32_at(bytes, ph + 12)?;
+        let filesz = u32_at(bytes, ph + 16)?;
+        let memsz = u32_at(bytes, ph + 20)?;
+        if memsz == 0 {
+            continue;
+        }
+        let len = memsz.max(filesz);
+        if paddr.checked_add(len).is_none_or(|end| end > ADDRESS_SPACE) {
+            return Err(LoadError::Invalid("segment outside the address space"));
+        }
+        let mut data = slice(bytes, offset, filesz)?.to_vec();
+        // .bss
+        data.resize(len as usize, 0);
+        image.push(paddr, &data);
+    }
+
+    let shoff = u32_at(bytes, 32)? as usize;
+    let shentsize = u16_at(bytes, 46)? as usize;
+    let shnum = u16_at(bytes, 48)? as usize;
+    let section = |idx: usize| shoff + idx * shentsize;
+    for i in 0..shnum {
+        let sh = section(i);
+        if u32_at(bytes, sh + 4)? != SHT_SYMTAB {
+            continue;
+        }
+        let symtab = slice(bytes, u32_at(bytes, sh + 16)?, u32_at(bytes, sh + 20)?)?;
+        let strtab_sh = section(u32_at(bytes, sh + 24)? as usize);
+        let strtab = slice(
+            bytes,
+            u32_at(bytes, strtab_sh + 16)?,
+            u32_at(bytes, strtab_sh + 20)?,
+        )?;
+        for sym in symtab.chunks_exact(16) {
+            let name = u32_at(sym, 0)? as usize;
+            let value = u32_at(sym, 4)?;
+            let kind = sym[12] & 0xF;
+            let shndx = u16_at(sym, 14)?;
+            if name == 0 || shndx == 0 || kind == STT_SECTION || kind == STT_FILE {
+                continue;
+            }
+            let name = strtab
+                .get(name..)
+                .and_then(|s| s.split(|b| *b == 0).next())
+                .ok_or(LoadError::Invalid("symbol name out of range"))?;
+            image.symbols.insert(&String::from_utf8_lossy(name), value);
+        }
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse;
+    use crate::loader::{LoadError, Segment};
+
+    fn be16(v: u16) -> [u8; 2] {
+        v.to_be_bytes()
+    }
+
+    fn be32(v: u32) -> [u8; 4] {
+        v.to_be_bytes()
+    }
+
+    /// Executable with one PT_LOAD segment of 4 bytes of code and 4 bytes of
+    /// .bss at 0x1000, and a symbol table defining `_start` and `counter`
+    fn executable() -> Vec<u8> {
+        let mut elf = vec![0x7F, b'E', b'L', b'F', 1, 2, 1];
+        elf.resize(16, 0);
+        elf.extend(be16(2)); // e_type
+        elf.extend(be16(4)); // e_machine
+        elf.extend(be32(1));
+        elf.extend(be32(0x1000)); // e_entry
+        elf.extend(be32(52)); // e_phoff
+        elf.extend(be32(0)); // e_shoff, patched below
+        elf.extend(be32(0));
+        elf.extend(be16(52));
+        elf.extend(be16(32));
+        elf.extend(be16(1)); // e_phnum
+        elf.extend(be16(40));
+        elf.extend(be16(3)); // e_shnum
+        elf.extend(be16(0));
+        // Program header
+        for v in [1, 84, 0x1000, 0x1000, 4, 8, 5, 2] {
+            elf.extend(be32(v));
+        }
+        // Code
+        elf.extend([0x70, 0x01, 0x4E, 0x75]);
+        // Symbols
+        let symtab = elf.len() as u32;
+        elf.extend([0; 16]);
+        for (name, value, info) in [(1, 0x1000, 0x12), (8, 0x1004, 0x11), (16, 0, 0x03)] {
+            elf.extend(be32(name));
+            elf.extend(be32(value));
+            elf.extend(be32(0));
+            elf.extend([info, 0]);
+            elf.extend(be16(1));
+        }
+        let strtab = elf.len() as u32;
+        elf.extend(b"\0_start\0counter\0\0");
+        let shoff = elf.len() as u32;
+        elf[32..36].copy_from_slice(&be32(shoff));
+        elf.extend([0; 40]);
+        for v in [0, 2, 0, 0, symtab, 64, 2, 1, 4, 16] {
+            elf.extend(be32(v));
+        }
+        for v in [0, 3, 0, 0, strtab, 17, 0, 0, 1, 0] {
+            elf.extend(be32(v));
+        }
+        elf
+    }
+
+    #[test]
+    fn test_parse() {
+        let image = parse(&executable()).unwrap();
+        assert_eq!(
+            image.segments,
+            [Segment {
+                addr: 0x1000,
+                data: vec![0x70, 0x01, 0x4E, 0x75, 0, 0, 0, 0],
+            }]
+        );
+        assert_eq!(image.entry, Some(0x1000));
+        assert_eq!(image.symbols.len(), 2);
+        assert_eq!(image.symbols.address("counter"), Some(0x1004));
+        assert_eq!(image.symbols.lookup(0x1002), Some(("_start", 2)));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut elf = executable();
+        elf[19] = 3; // EM_386
+        assert!(matches!(parse(&elf), Err(LoadError::Invalid(_))));
+        let elf = executable();
+        assert!(matches!(parse(&elf[..100]), Err(LoadError::Invalid(_))));
+        // .bss past the end of the address space
+        for memsz in [0x00FF_F001u32, 0xFFFF_FFFF] {
+            let mut elf = executable();
+            elf[72..76].copy_from_slice(&memsz.to_be_bytes());
+            assert!(matches!(parse(&elf), Err(LoadError::Invalid(_))));
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use super::{Image, LoadError, SymbolTable, ADDRESS_SPACE};
+
+pub(super) const MAGIC: [u8; 4] = [0, 0, 0x03, 0xF3];
+
+/// Default address of the first hunk, with the others following it
+pub(super) const BASE: u32 = 0x1000;
+
+const HUNK_NAME: u32 = 0x3E8;
+const HUNK_CODE: u32 = 0x3E9;
+const HUNK_DATA: u32 = 0x3EA;
+const HUNK_BSS: u32 = 0x3EB;
+const HUNK_RELOC32: u32 = 0x3EC;
+const HUNK_SYMBOL: u32 = 0x3F0;
+const HUNK_DEBUG: u32 = 0x3F1;
+const HUNK_END: u32 = 0x3F2;
+const HUNK_HEADER: u32 = 0x3F3;
+/// Memory type flags in the upper bits of hunk sizes and types
+const MEMF: u32 = 0xC000_0000;
+/// Line debug information, as written by SAS/C and vlink `-hunkdebug`
+const DEBUG_LINE: u32 = u32::from_be_bytes(*b"LINE");
+
+const MISSING: LoadError = LoadError::Invalid("more hunks than the header declares");
+
+/// Cursor over the longs of a hunk file
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn long(&mut self) -> Result<u32, LoadError> {
+        let b = self.longs(1)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Bytes of the next `n` longs
+    fn longs(&mut self, n: u32) -> Result<&'a [u8], LoadError> {
+        let end = (n as usize)
+            .checked_mul(4)
+            .and_then(|len| len.checked_add(self.pos))
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(LoadError::Invalid("truncated hunk file"))?;
+        let longs = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(longs)
+    }
+
+    /// Name padded with zeros to `n` longs
+    fn name(&mut self, n: u32) -> Result<String, LoadError> {
+        let name = self.longs(n)?;
+        let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        Ok(String::from_utf8_lossy(&name[..len]).into_owned())
+    }
+}
+
+/// Hunk placed in memory
+struct Hunk {
+    addr: u32,
+    data: Vec<u8>,
+}
+
+/// Parse an AmigaOS hunk executable, placing its hunks one after another
+/// from `base`. Hunks are zero filled up to the size given in the header,
+/// relocations are resolved against the addresses the hunks end up at and
+/// execution starts at the first hunk. Symbols come from HUNK_SYMBOL
+/// blocks, and source lines in HUNK_DEBUG line tables become `file:line`
+/// symbols.
+pub(super) fn parse(bytes: &[u8], base: u32) -> Result<Image, LoadError> {
+    let mut reader = Reader::new(bytes);
+    if reader.long()? != HUNK_HEADER {
+        return Err(LoadError::Invalid("not an Amiga hunk file"));
+    }
+    if reader.long()? != 0 {
+        return Err(LoadError::Invalid("resident libraries are not supported"));
+    }
+    reader.long()?;
+    let first = reader.long()?;
+    let last = reader.long()?;
+    let mut hunks = Vec::new();
+    let mut addr = base;
+    for _ in first..=last {
+        let size = reader.long()?;
+        if size & MEMF == MEMF {
+            // Extended memory attributes
+            reader.long()?;
+        }
+        let end = (size & !MEMF)
+            .checked_mul(4)
+            .and_then(|len| addr.checked_add(len))
+            .filter(|end| *end <= ADDRESS_SPACE)
+            .ok_or(LoadError::Invalid("hunks do not fit in the address space"))?;
+        hunks.push(Hunk {
+            addr,
+            data: vec![0; (end - addr) as usize],
+        });
+        addr = end;
+    }
+
+    let mut image = Image {
+        entry: hunks.first().map(|hunk| hunk.addr),
+        ..Default::default()
+    };
+    let mut current = 0;
+    while !reader.is_empty() {
+        match reader.long()? & !MEMF {
+            HUNK_NAME => {
+                let n = reader.long()?;
+                reader.longs(n)?;
+            }
+            HUNK_CODE | HUNK_DATA => {
+                let n = reader.long()? & !MEMF;
+                let contents = reader.longs(n)?;
+                hunks
+                    .get_mut(current)
+                    .ok_or(MISSING)?
+                    .data
+                    .get_mut(..contents.len())
+                    .ok_or(LoadError::Invalid("hunk larger than its allocation"))?
+                    .copy_from_slice(contents);
+            }
+            HUNK_BSS => {
+                reader.long()?;
+            }
+            HUNK_RELOC32 => loop {
+                let n = reader.long()?;
+                if n == 0 {
+                    break;
+                }
+                let target = hunks
+                    .get(reader.long()? as usize)
+                    .ok_or(LoadError::Invalid("relocation to a missing hunk"))?
+                    .addr;
+                let hunk = hunks.get_mut(current).ok_or(MISSING)?;
+                for offset in reader.longs(n)?.chunks_exact(4) {
+                    let offset = u32::from_be_bytes([offset[0], offset[1], offset[2], offset[3]]);
+                    let long = (offset as usize)
+                        .checked_add(4)
+                        .and_then(|end| hunk.data.get_mut(offset as usize..end))
+                        .ok_or(LoadError::Invalid("relocation outside its hunk"))?;
+                    let val = u32::from_be_bytes([long[0], long[1], long[2], long[3]]);
+                    long.copy_from_slice(&val.wrapping_add(target).to_be_bytes());
+                }
+            },
+            HUNK_SYMBOL => {
+                let hunk = hunks.get(current).ok_or(MISSING)?.addr;
+                loop {
+                    let n = reader.long()?;
+                    if n == 0 {
+                        break;
+                    }
+                    // The upper byte holds the symbol type in object files
+                    let name = reader.name(n & 0xFF_FFFF)?;
+                    let value = reader.long()?;
+                    image.symbols.insert(&name, hunk.wrapping_add(value));
+                }
+            }
+            HUNK_DEBUG => {
+                let hunk = hunks.get(current).ok_or(MISSING)?.addr;
+                let n = reader.long()?;
+                line_debug(Reader::new(reader.longs(n)?), hunk, &mut image.symbols)?;
+            }
+            HUNK_END => current += 1,
+            _ => return Err(LoadError::Invalid("unsupported hunk type")),
+        }
+    }
+
+    for hunk in hunks {
+        image.push(hunk.addr, &hunk.data);
+    }
+    Ok(image)
+}
+
+/// Add the lines of a LINE debug block as `file:line` symbols. Blocks in
+/// other debug formats are skipped.
+fn line_debug(mut debug: Reader, hunk: u32, symbols: &mut SymbolTable) -> Result<(), LoadError> {
+    let start = hunk.wrapping_add(debug.long()?);
+    if debug.long()? != DEBUG_LINE {
+        return Ok(());
+    }
+    let n = debug.long()?;
+    let file = debug.name(n)?;
+    while !debug.is_empty() {
+        // The upper byte can hold the column
+        let line = debug.long()? & 0xFF_FFFF;
+        let offset = debug.long()?;
+        symbols.insert(&format!("{file}:{line}"), start.wrapping_add(offset));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse;
+    use crate::loader::{LoadError, Segment};
+
+    /// Executable with a code hunk referencing a data hunk, which points
+    /// back into the code, followed by a .bss hunk in chip memory
+    fn executable() -> Vec<u8> {
+        let blocks: [&[u32]; 9] = [
+            // Header
+            &[0x3F3, 0, 3, 0, 2, 2, 1, 0x4000_0002],
+            // Code, relocated to the data hunk
+            &[0x3E9, 2, 0x41F9_0000, 0x0000_4E75],
+            &[0x3EC, 1, 1, 2, 0],
+            // `_main` and lines 3 and 4 of demo.c
+            &[0x3F0, 2, 0x5F6D_6169, 0x6E00_0000, 0, 0],
+            &[
+                0x3F1,
+                9,
+                0,
+                0x4C49_4E45,
+                2,
+                0x6465_6D6F,
+                0x2E63_0000,
+                3,
+                0,
+                4,
+                6,
+            ],
+            &[0x3F2],
+            // Data, relocated to the code hunk
+            &[0x3EA, 1, 4, 0x3EC, 1, 0, 0, 0, 0x3F2],
+            &[0x3EB, 2],
+            &[0x3F2],
+        ];
+        blocks
+            .concat()
+            .iter()
+            .flat_map(|long| long.to_be_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let image = parse(&executable(), 0x2000).unwrap();
+        assert_eq!(
+            image.segments,
+            [Segment {
+                addr: 0x2000,
+                data: vec![
+                    0x41, 0xF9, 0, 0, 0x20, 0x08, 0x4E, 0x75, // Code
+                    0, 0, 0x20, 0x04, // Data
+                    0, 0, 0, 0, 0, 0, 0, 0, // Bss
+                ],
+            }]
+        );
+        assert_eq!(image.entry, Some(0x2000));
+        assert_eq!(image.symbols.name(0x2000), Some("_main"));
+        assert_eq!(image.symbols.address("demo.c:3"), Some(0x2000));
+        assert_eq!(image.symbols.lookup(0x2007), Some(("demo.c:4", 1)));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let hunks = executable();
+        assert!(matches!(parse(&hunks[..60], 0), Err(LoadError::Invalid(_))));
+        // Relocation past the end of the code hunk
+        let mut hunks = executable();
+        hunks[63] = 6;
+        assert!(matches!(parse(&hunks, 0), Err(LoadError::Invalid(_))));
+        assert!(matches!(
+            parse(&[0, 0, 0x03, 0xE7], 0),
+            Err(LoadError::Invalid(_))
+        ));
+        // .bss hunk past the end of the address space
+        for size in [0x003F_FC00u32, 0x3FFF_FFFF] {
+            let mut hunks = executable();
+            hunks[28..32].copy_from_slice(&size.to_be_bytes());
+            assert!(matches!(parse(&hunks, 0x1000), Err(LoadError::Invalid(_))));
+        }
+    }
+}
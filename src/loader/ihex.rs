@@ -0,0 +1,105 @@
+use super::{decode_hex, Image, LoadError};
+
+/// Whether `bytes` look like Intel HEX, a text file starting with a record
+/// mark
+pub(super) fn is_intel_hex(bytes: &[u8]) -> bool {
+    let text = bytes.trim_ascii_start();
+    text.len() > 11 && text[0] == b':' && text.is_ascii()
+}
+
+/// Parse Intel HEX records. Data records are offset by the last extended
+/// segment or linear address record, and a start segment or start linear
+/// address record gives the entry point. Parsing stops at the end of file
+/// record.
+pub(super) fn parse(bytes: &[u8]) -> Result<Image, LoadError> {
+    let text = std::str::from_utf8(bytes).map_err(|_| LoadError::Malformed {
+        line: 1,
+        reason: "not a text file",
+    })?;
+    let mut image = Image::default();
+    let mut base = 0u32;
+    for (idx, record) in text.lines().enumerate() {
+        let line = idx + 1;
+        let malformed = |reason| LoadError::Malformed { line, reason };
+        let record = record.trim();
+        if record.is_empty() {
+            continue;
+        }
+        let hex = record
+            .strip_prefix(':')
+            .ok_or(malformed("missing record mark"))?;
+        let bytes = decode_hex(hex).ok_or(malformed("invalid hex digits"))?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(malformed("record length mismatch"));
+        }
+        if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+            return Err(LoadError::Checksum { line });
+        }
+        let offset = u32::from(u16::from_be_bytes([bytes[1], bytes[2]]));
+        let data = &bytes[4..bytes.len() - 1];
+        let value = || data.iter().fold(0u32, |a, b| (a << 8) | u32::from(*b));
+        match (bytes[3], data.len()) {
+            (0x00, _) => image.push(base.wrapping_add(offset), data),
+            (0x01, _) => break,
+            (0x02, 2) => base = value() << 4,
+            (0x03, 4) => image.entry = Some(((value() >> 16) << 4) + (value() & 0xFFFF)),
+            (0x04, 2) => base = value() << 16,
+            (0x05, 4) => image.entry = Some(value()),
+            (0x02..=0x05, _) => return Err(malformed("record length mismatch")),
+            _ => return Err(malformed("unknown record type")),
+        }
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_intel_hex, parse};
+    use crate::loader::{LoadError, Segment};
+
+    #[test]
+    fn test_parse() {
+        let text = ":040000007001720217\n\
+                    :020000021000EC\n\
+                    :020000004E713F\n\
+                    :0200000400FEFC\n\
+                    :020010004E712F\n\
+                    :0400000500001000E7\n\
+                    :00000001FF\n";
+        assert!(is_intel_hex(text.as_bytes()));
+        let image = parse(text.as_bytes()).unwrap();
+        assert_eq!(
+            image.segments,
+            [
+                Segment {
+                    addr: 0,
+                    data: vec![0x70, 0x01, 0x72, 0x02],
+                },
+                Segment {
+                    addr: 0x10000,
+                    data: vec![0x4E, 0x71],
+                },
+                Segment {
+                    addr: 0xFE0010,
+                    data: vec![0x4E, 0x71],
+                },
+            ]
+        );
+        assert_eq!(image.entry, Some(0x1000));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            parse(b":0400000070017202FF\n"),
+            Err(LoadError::Checksum { line: 1 })
+        ));
+        // Anything after the end of file record is ignored
+        assert!(parse(b":00000001FF\n0400").is_ok());
+        assert!(matches!(
+            parse(b":020000004E713F\n:04000000700172\n"),
+            Err(LoadError::Malformed { line: 2, .. })
+        ));
+        assert!(!is_intel_hex(&[0x4E, 0x71]));
+    }
+}
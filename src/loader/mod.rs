@@ -0,0 +1,282 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::Path,
+};
+
+mod elf;
+mod hunk;
+mod ihex;
+mod prg;
+mod spec;
+mod srec;
+
+pub use spec::LoadSpec;
+
+/// End of the 24 bit address space, which no image may extend past
+const ADDRESS_SPACE: u32 = 0x100_0000;
+
+/// Bytes to be placed at `addr`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub addr: u32,
+    pub data: Vec<u8>,
+}
+
+/// Program parsed from an executable or object file, ready to be placed in
+/// memory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Image {
+    pub segments: Vec<Segment>,
+    /// Address to start executing at, if the file specifies one
+    pub entry: Option<u32>,
+    /// Initial stack pointer, for formats that set up a stack
+    pub stack: Option<u32>,
+    pub symbols: SymbolTable,
+}
+
+/// Addresses of named symbols, searchable by name or by address
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    by_name: HashMap<String, u32>,
+    /// First name defined at each address
+    by_addr: BTreeMap<u32, String>,
+}
+
+impl SymbolTable {
+    pub fn insert(&mut self, name: &str, addr: u32) {
+        self.by_name.insert(name.to_string(), addr);
+        self.by_addr.entry(addr).or_insert_with(|| name.to_string());
+    }
+
+    /// Add every symbol of `other`
+    pub fn extend(&mut self, other: &SymbolTable) {
+        for (name, addr) in &other.by_name {
+            self.insert(name, *addr);
+        }
+    }
+
+    pub fn address(&self, name: &str) -> Option<u32> {
+        self.by_name.get(name).copied()
+    }
+
+    /// Name of the symbol at exactly `addr`
+    pub fn name(&self, addr: u32) -> Option<&str> {
+        self.by_addr.get(&addr).map(String::as_str)
+    }
+
+    /// Closest symbol at or below `addr` and the offset of `addr` from it
+    pub fn lookup(&self, addr: u32) -> Option<(&str, u32)> {
+        self.by_addr
+            .range(..=addr)
+            .next_back()
+            .map(|(start, name)| (name.as_str(), addr - start))
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+}
+
+impl Image {
+    /// Add `data` at `addr`, extending the last segment when contiguous
+    pub(crate) fn push(&mut self, addr: u32, data: &[u8]) {
+        match self.segments.last_mut() {
+            Some(last) if last.addr.wrapping_add(last.data.len() as u32) == addr => {
+                last.data.extend_from_slice(data)
+            }
+            _ => self.segments.push(Segment {
+                addr,
+                data: data.to_vec(),
+            }),
+        }
+    }
+
+    /// Number of bytes in all segments
+    pub fn size(&self) -> usize {
+        self.segments.iter().map(|s| s.data.len()).sum()
+    }
+
+    /// Read and parse the file at `path`, detecting its format
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(LoadError::Io)?;
+        let format = Format::detect(path, &bytes);
+        Self::parse(format, &bytes)
+    }
+
+    pub fn parse(format: Format, bytes: &[u8]) -> Result<Self, LoadError> {
+        match format {
+            Format::Binary => Ok(Self {
+                segments: vec![Segment {
+                    addr: 0,
+                    data: bytes.to_vec(),
+                }],
+                ..Default::default()
+            }),
+            Format::SRecord => srec::parse(bytes),
+            Format::IntelHex => ihex::parse(bytes),
+            Format::Elf => elf::parse(bytes),
+            Format::TosPrg => prg::parse(bytes, prg::BASE),
+            Format::AmigaHunk => hunk::parse(bytes, hunk::BASE),
+        }
+    }
+
+    /// Parse `bytes` with a raw binary placed at `addr`, or a relocatable
+    /// executable relocated to `addr`. Formats that give their own
+    /// addresses cannot be moved.
+    pub fn parse_at(format: Format, bytes: &[u8], addr: u32) -> Result<Self, LoadError> {
+        match format {
+            Format::Binary => {
+                let mut image = Self::default();
+                image.push(addr, bytes);
+                Ok(image)
+            }
+            Format::TosPrg => prg::parse(bytes, addr),
+            Format::AmigaHunk => hunk::parse(bytes, addr),
+            Format::SRecord | Format::IntelHex | Format::Elf => {
+                Err(LoadError::Invalid("file has fixed load addresses"))
+            }
+        }
+    }
+
+    /// Copy of the segments, moved so that the lowest one starts at `addr`
+    pub fn mirror(&self, addr: u32) -> Self {
+        let base = self.segments.iter().map(|s| s.addr).min().unwrap_or(0);
+        Self {
+            segments: self
+                .segments
+                .iter()
+                .map(|s| Segment {
+                    addr: s.addr.wrapping_sub(base).wrapping_add(addr),
+                    data: s.data.clone(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// First address that both images place bytes at
+    pub fn overlap(&self, other: &Image) -> Option<u32> {
+        self.segments
+            .iter()
+            .flat_map(|a| other.segments.iter().map(move |b| (a, b)))
+            .find_map(|(a, b)| {
+                let start = a.addr.max(b.addr);
+                (u64::from(start) < a.end().min(b.end())).then_some(start)
+            })
+    }
+}
+
+impl Segment {
+    /// Address past the last byte, which can be past the 32 bit space
+    fn end(&self) -> u64 {
+        u64::from(self.addr) + self.data.len() as u64
+    }
+}
+
+/// File formats the loader understands
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// Raw memory image loaded at address 0
+    Binary,
+    /// Motorola S-records
+    SRecord,
+    /// Intel HEX records
+    IntelHex,
+    /// 32 bit big endian ELF executable
+    Elf,
+    /// Atari TOS executable, relocated to a default address
+    TosPrg,
+    /// AmigaOS hunk executable, placed at a default address
+    AmigaHunk,
+}
+
+impl Format {
+    /// Guess the format of `bytes` from the extension of `path`, falling back
+    /// to the contents and then to a raw binary
+    pub fn detect(path: &Path, bytes: &[u8]) -> Self {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        if bytes.starts_with(&elf::MAGIC) {
+            return Self::Elf;
+        }
+        if bytes.starts_with(&hunk::MAGIC) {
+            return Self::AmigaHunk;
+        }
+        if prg::is_prg(bytes) {
+            return Self::TosPrg;
+        }
+        match ext.as_deref() {
+            Some("s19" | "s28" | "s37" | "srec" | "mot" | "sx") => Self::SRecord,
+            Some("hex" | "ihx" | "ihex") => Self::IntelHex,
+            _ if srec::is_srecord(bytes) => Self::SRecord,
+            _ if ihex::is_intel_hex(bytes) => Self::IntelHex,
+            _ => Self::Binary,
+        }
+    }
+}
+
+/// Reason a file could not be loaded
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    /// Record checksum mismatch on `line`
+    Checksum {
+        line: usize,
+    },
+    /// Contents do not follow the format
+    Malformed {
+        line: usize,
+        reason: &'static str,
+    },
+    /// Binary file that is corrupt or not for the 68000
+    Invalid(&'static str),
+    /// Image from `second` placed over the one from `first`, starting at
+    /// `addr`
+    Overlap {
+        first: String,
+        second: String,
+        addr: u32,
+    },
+    /// Image placed at `addr`, where no memory is mapped
+    Unmapped {
+        addr: u32,
+    },
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Checksum { line } => write!(f, "bad checksum on line {line}"),
+            Self::Malformed { line, reason } => write!(f, "{reason} on line {line}"),
+            Self::Invalid(reason) => write!(f, "{reason}"),
+            Self::Overlap {
+                first,
+                second,
+                addr,
+            } => write!(f, "{second} overlaps {first} at {addr:#X}"),
+            Self::Unmapped { addr } => write!(f, "no memory mapped at {addr:#X}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Bytes encoded as pairs of hex digits
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,230 @@
+use super::{Image, LoadError, ADDRESS_SPACE};
+
+pub(super) const MAGIC: [u8; 2] = [0x60, 0x1A];
+
+/// Default address of the basepage, with the text segment following it
+pub(super) const BASE: u32 = 0x1000;
+const HEADER_LEN: usize = 28;
+const BASEPAGE_LEN: u32 = 0x100;
+/// Stack given to the program above its .bss
+const STACK_SIZE: u32 = 0x4000;
+
+const SYM_BSS: u16 = 0x0100;
+const SYM_TEXT: u16 = 0x0200;
+const SYM_DATA: u16 = 0x0400;
+const SYM_DEFINED: u16 = 0x8000;
+/// GST extended name, continued in the following entry
+const SYM_LONG_NAME: u16 = 0x0048;
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16, LoadError> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or(LoadError::Invalid("truncated PRG file"))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, LoadError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(LoadError::Invalid("truncated PRG file"))
+}
+
+/// Whether `bytes` start with a PRG header whose segments fit in the file
+pub(super) fn is_prg(bytes: &[u8]) -> bool {
+    let (Ok(text), Ok(data), Ok(syms)) = (u32_at(bytes, 2), u32_at(bytes, 6), u32_at(bytes, 14))
+    else {
+        return false;
+    };
+    bytes.starts_with(&MAGIC)
+        && [text, data, syms]
+            .iter()
+            .try_fold(HEADER_LEN, |len, n| len.checked_add(*n as usize))
+            .is_some_and(|len| len <= bytes.len())
+}
+
+/// Parse an Atari TOS executable and relocate it to run with its basepage
+/// at `base`. The text and data segments follow the basepage, then the
+/// zero filled .bss and a stack holding the basepage address as TOS passes
+/// it. Symbols are taken as offsets from the start of the text segment, as
+/// written by `vlink -tos-textbased`.
+pub(super) fn parse(bytes: &[u8], base: u32) -> Result<Image, LoadError> {
+    if !is_prg(bytes) {
+        return Err(LoadError::Invalid("not a TOS executable"));
+    }
+    let text_len = u32_at(bytes, 2)?;
+    let data_len = u32_at(bytes, 6)?;
+    let bss_len = u32_at(bytes, 10)?;
+    let syms_len = u32_at(bytes, 14)?;
+    let absolute = u16_at(bytes, 26)? != 0;
+
+    let top = [BASEPAGE_LEN, text_len, data_len, bss_len, STACK_SIZE + 1]
+        .iter()
+        .try_fold(base, |end, len| end.checked_add(*len))
+        .map(|end| end & !1)
+        .filter(|top| *top <= ADDRESS_SPACE)
+        .ok_or(LoadError::Invalid(
+            "program does not fit in the address space",
+        ))?;
+    let text = base + BASEPAGE_LEN;
+    let data = text + text_len;
+    let bss = data + data_len;
+    let stack = top - 8;
+
+    let mut memory = vec![0; (top - base) as usize];
+    let contents = &bytes[HEADER_LEN..HEADER_LEN + (text_len + data_len) as usize];
+    memory[BASEPAGE_LEN as usize..][..contents.len()].copy_from_slice(contents);
+    let basepage = [
+        base,
+        top,
+        text,
+        text_len,
+        data,
+        data_len,
+        bss,
+        bss_len,     // Segments
+        base + 0x80, // DTA, sharing space with the empty command line
+    ];
+    for (i, val) in basepage.iter().enumerate() {
+        memory[i * 4..][..4].copy_from_slice(&val.to_be_bytes());
+    }
+    // Return address of 0 and the basepage address as the argument
+    memory[(stack - base) as usize + 4..][..4].copy_from_slice(&base.to_be_bytes());
+
+    let syms = HEADER_LEN + (text_len + data_len) as usize;
+    let fixups = syms + syms_len as usize;
+    let first = u32_at(bytes, fixups).unwrap_or(0);
+    if !absolute && first != 0 {
+        let mut offset = first;
+        let mut table = bytes[fixups + 4..].iter();
+        'fixups: loop {
+            if offset
+                .checked_add(4)
+                .is_none_or(|end| end > text_len + data_len)
+            {
+                return Err(LoadError::Invalid("relocation outside the program"));
+            }
+            let at = (BASEPAGE_LEN + offset) as usize;
+            let long = &mut memory[at..at + 4];
+            let val = u32::from_be_bytes([long[0], long[1], long[2], long[3]]);
+            long.copy_from_slice(&val.wrapping_add(text).to_be_bytes());
+            loop {
+                match table.next().copied().unwrap_or(0) {
+                    0 => break 'fixups,
+                    1 => offset = offset.saturating_add(254),
+                    n => {
+                        offset = offset.saturating_add(n.into());
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut image = Image {
+        entry: Some(text),
+        stack: Some(stack),
+        ..Default::default()
+    };
+    image.push(base, &memory);
+
+    let mut entries = bytes[syms..fixups].chunks_exact(14);
+    while let Some(entry) = entries.next() {
+        let kind = u16_at(entry, 8)?;
+        let value = u32_at(entry, 10)?;
+        let mut name = entry[..8].to_vec();
+        if kind & SYM_LONG_NAME == SYM_LONG_NAME {
+            name.extend(entries.next().unwrap_or_default());
+        }
+        if kind & SYM_DEFINED == 0 || kind & (SYM_TEXT | SYM_DATA | SYM_BSS) == 0 {
+            continue;
+        }
+        let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        image.symbols.insert(
+            &String::from_utf8_lossy(&name[..len]),
+            text.wrapping_add(value),
+        );
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_prg, parse};
+    use crate::loader::LoadError;
+
+    /// Program with a relocated immediate in the text segment and a
+    /// relocated pointer in the data segment
+    fn program() -> Vec<u8> {
+        let mut prg = vec![0x60, 0x1A];
+        for len in [8, 8, 4, 42, 0] {
+            prg.extend((len as u32).to_be_bytes());
+        }
+        prg.extend([0; 6]);
+        // Text
+        prg.extend([0x20, 0x3C, 0, 0, 0, 8, 0x4E, 0x75]);
+        // Data
+        prg.extend([0, 0, 0, 4, 0, 0, 0, 9]);
+        // Symbols
+        prg.extend(b"main\0\0\0\0\xA2\0\0\0\0\0");
+        prg.extend(b"a_long_s\x84\x48\0\0\0\x08");
+        prg.extend(b"ymbol_name\0\0\0\0");
+        // Fixups
+        prg.extend([0, 0, 0, 2, 6, 0]);
+        prg
+    }
+
+    #[test]
+    fn test_parse() {
+        let prg = program();
+        assert!(is_prg(&prg));
+        let image = parse(&prg, 0x2000).unwrap();
+        assert_eq!(image.entry, Some(0x2100));
+        assert_eq!(image.stack, Some(0x610C));
+        let segment = &image.segments[0];
+        assert_eq!(segment.addr, 0x2000);
+        let long = |addr: u32| {
+            let i = (addr - 0x2000) as usize;
+            u32::from_be_bytes(segment.data[i..i + 4].try_into().unwrap())
+        };
+        // Basepage
+        assert_eq!(long(0x2004), 0x6114);
+        assert_eq!(long(0x2008), 0x2100);
+        assert_eq!(long(0x2018), 0x2110);
+        assert_eq!(long(0x201C), 4);
+        // Relocated text and data
+        assert_eq!(long(0x2102), 0x2108);
+        assert_eq!(long(0x2108), 0x2104);
+        assert_eq!(long(0x210C), 9);
+        assert_eq!(long(0x2110), 0);
+        // Basepage argument on the stack
+        assert_eq!(long(0x6110), 0x2000);
+        assert_eq!(image.symbols.address("main"), Some(0x2100));
+        assert_eq!(image.symbols.address("a_long_symbol_name"), Some(0x2108));
+    }
+
+    #[test]
+    fn test_absolute() {
+        let mut prg = program();
+        prg[27] = 1;
+        let image = parse(&prg, 0x2000).unwrap();
+        assert_eq!(image.segments[0].data[0x102..0x106], [0, 0, 0, 8]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let prg = program();
+        assert!(!is_prg(&prg[..40]));
+        assert!(matches!(parse(&prg[..40], 0), Err(LoadError::Invalid(_))));
+        let mut prg = program();
+        let len = prg.len();
+        prg[len - 2] = 20;
+        assert!(matches!(parse(&prg, 0), Err(LoadError::Invalid(_))));
+        // .bss too large for the address space or wrapping around it
+        for bss in [0x7FFF_0000u32, 0xFFFF_FF00] {
+            let mut prg = program();
+            prg[10..14].copy_from_slice(&bss.to_be_bytes());
+            assert!(matches!(parse(&prg, 0x1000), Err(LoadError::Invalid(_))));
+        }
+    }
+}
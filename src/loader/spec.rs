@@ -0,0 +1,148 @@
+use std::{path::Path, str::FromStr};
+
+use super::{Format, Image, LoadError};
+
+/// File to load and where to put it, written `FILE[@ADDR[,MIRROR...]]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadSpec {
+    pub path: String,
+    /// Address of a raw binary or relocatable executable, which otherwise
+    /// goes at the default address of its format
+    pub addr: Option<u32>,
+    /// Addresses a copy of the image is also placed at
+    pub mirrors: Vec<u32>,
+}
+
+impl FromStr for LoadSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((path, addrs)) = s.rsplit_once('@') else {
+            return Ok(Self {
+                path: s.to_string(),
+                addr: None,
+                mirrors: Vec::new(),
+            });
+        };
+        let mut addrs = addrs.split(',').map(parse_addr);
+        Ok(Self {
+            path: path.to_string(),
+            addr: addrs.next().transpose()?,
+            mirrors: addrs.collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Hex address, with or without a `0x` prefix
+fn parse_addr(s: &str) -> Result<u32, String> {
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u32::from_str_radix(digits, 16).map_err(|_| format!("invalid address `{s}`"))
+}
+
+impl LoadSpec {
+    /// Read and parse the file, followed by a copy for each mirror
+    pub fn load(&self) -> Result<Vec<Image>, LoadError> {
+        let bytes = std::fs::read(&self.path).map_err(|e| {
+            LoadError::Io(std::io::Error::new(e.kind(), format!("{}: {e}", self.path)))
+        })?;
+        let format = Format::detect(Path::new(&self.path), &bytes);
+        let image = match self.addr {
+            Some(addr) => Image::parse_at(format, &bytes, addr)?,
+            None => Image::parse(format, &bytes)?,
+        };
+        let mirrors: Vec<_> = self.mirrors.iter().map(|m| image.mirror(*m)).collect();
+        Ok(std::iter::once(image).chain(mirrors).collect())
+    }
+
+    /// Load every file in order, failing if any of the images or their
+    /// mirrors overlap
+    pub fn load_all(specs: &[LoadSpec]) -> Result<Vec<Image>, LoadError> {
+        let mut loaded: Vec<(&str, Image)> = Vec::new();
+        for spec in specs {
+            for image in spec.load()? {
+                let overlap = loaded
+                    .iter()
+                    .find_map(|(path, other)| Some((*path, other.overlap(&image)?)));
+                if let Some((first, addr)) = overlap {
+                    return Err(LoadError::Overlap {
+                        first: first.to_string(),
+                        second: spec.path.clone(),
+                        addr,
+                    });
+                }
+                loaded.push((&spec.path, image));
+            }
+        }
+        Ok(loaded.into_iter().map(|(_, image)| image).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LoadSpec;
+    use crate::loader::{Image, LoadError, Segment};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "rom.bin@0xFC0000,0".parse(),
+            Ok(LoadSpec {
+                path: "rom.bin".into(),
+                addr: Some(0xFC0000),
+                mirrors: vec![0],
+            })
+        );
+        assert_eq!(
+            "prog.bin@1000".parse::<LoadSpec>().unwrap().addr,
+            Some(0x1000)
+        );
+        assert_eq!("prog.bin".parse::<LoadSpec>().unwrap().addr, None);
+        assert!("prog.bin@0xZZ".parse::<LoadSpec>().is_err());
+    }
+
+    #[test]
+    fn test_mirror_overlap() {
+        let mut image = Image::default();
+        image.push(0x1000, &[1, 2]);
+        image.push(0x1004, &[3, 4]);
+        let mirror = image.mirror(0x1002);
+        assert_eq!(
+            mirror.segments,
+            [
+                Segment {
+                    addr: 0x1002,
+                    data: vec![1, 2],
+                },
+                Segment {
+                    addr: 0x1006,
+                    data: vec![3, 4],
+                },
+            ]
+        );
+        assert_eq!(image.overlap(&mirror), None);
+        assert_eq!(image.overlap(&image.mirror(0x1003)), Some(0x1004));
+        assert_eq!(image.overlap(&image.mirror(0x1006)), None);
+    }
+
+    #[test]
+    fn test_load_all() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("phoenix-load-{}.bin", std::process::id()));
+        std::fs::write(&path, [0x4E, 0x71, 0x4E, 0x71]).unwrap();
+        let path = path.to_str().unwrap();
+        let spec = |s: String| s.parse::<LoadSpec>().unwrap();
+
+        let images =
+            LoadSpec::load_all(&[spec(format!("{path}@FC0000,0")), spec(format!("{path}@8"))])
+                .unwrap();
+        let addrs: Vec<_> = images.iter().map(|i| i.segments[0].addr).collect();
+        assert_eq!(addrs, [0xFC0000, 0, 8]);
+
+        let result = LoadSpec::load_all(&[spec(format!("{path}@0")), spec(format!("{path}@2"))]);
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(result, Err(LoadError::Overlap { addr: 2, .. })));
+    }
+}
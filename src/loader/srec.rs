@@ -0,0 +1,115 @@
+use super::{decode_hex, Image, LoadError};
+
+/// Whether `bytes` look like S-records, a text file starting with an S0-S3
+/// record
+pub(super) fn is_srecord(bytes: &[u8]) -> bool {
+    let text = bytes.trim_ascii_start();
+    text.len() > 2 && text[0] == b'S' && (b'0'..=b'3').contains(&text[1]) && text.is_ascii()
+}
+
+/// Parse Motorola S-records. S1-S3 data records are placed at their
+/// address and an S7-S9 record gives the entry point. Header and count
+/// records are checked but otherwise ignored.
+pub(super) fn parse(bytes: &[u8]) -> Result<Image, LoadError> {
+    let text = std::str::from_utf8(bytes).map_err(|_| LoadError::Malformed {
+        line: 1,
+        reason: "not a text file",
+    })?;
+    let mut image = Image::default();
+    for (idx, record) in text.lines().enumerate() {
+        let line = idx + 1;
+        let malformed = |reason| LoadError::Malformed { line, reason };
+        let record = record.trim();
+        if record.is_empty() {
+            continue;
+        }
+        if !record.is_ascii() {
+            return Err(malformed("non-ASCII characters"));
+        }
+        let kind = match record.as_bytes() {
+            [b'S', kind, ..] if kind.is_ascii_digit() => kind - b'0',
+            [b'S', ..] => return Err(malformed("unknown record type")),
+            _ => return Err(malformed("missing record start")),
+        };
+        let bytes = decode_hex(&record[2..]).ok_or(malformed("invalid hex digits"))?;
+        let (&count, rest) = bytes.split_first().ok_or(malformed("record too short"))?;
+        if rest.len() != count as usize || count == 0 {
+            return Err(malformed("record length mismatch"));
+        }
+        let sum = bytes[..bytes.len() - 1]
+            .iter()
+            .fold(0u8, |sum, b| sum.wrapping_add(*b));
+        if !sum != bytes[bytes.len() - 1] {
+            return Err(LoadError::Checksum { line });
+        }
+        let addr_len = match kind {
+            0 | 1 | 5 | 9 => 2,
+            2 | 6 | 8 => 3,
+            3 | 7 => 4,
+            _ => return Err(malformed("unknown record type")),
+        };
+        let body = &rest[..rest.len() - 1];
+        if body.len() < addr_len {
+            return Err(malformed("record too short"));
+        }
+        let (addr, data) = body.split_at(addr_len);
+        let addr = addr.iter().fold(0u32, |a, b| (a << 8) | u32::from(*b));
+        match kind {
+            1..=3 => image.push(addr, data),
+            7..=9 => image.entry = Some(addr),
+            _ => {}
+        }
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_srecord, parse};
+    use crate::loader::{LoadError, Segment};
+
+    #[test]
+    fn test_parse() {
+        let text = "S00600004844521B\n\
+                    S10710007001720203\n\
+                    S10510044E7127\n\
+                    S2060200004E7138\n\
+                    S5030003F9\n\
+                    S9031000EC\n";
+        assert!(is_srecord(text.as_bytes()));
+        let image = parse(text.as_bytes()).unwrap();
+        assert_eq!(
+            image.segments,
+            [
+                Segment {
+                    addr: 0x1000,
+                    data: vec![0x70, 0x01, 0x72, 0x02, 0x4E, 0x71],
+                },
+                Segment {
+                    addr: 0x20000,
+                    data: vec![0x4E, 0x71],
+                },
+            ]
+        );
+        assert_eq!(image.entry, Some(0x1000));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            parse(b"S10710007001720204\n"),
+            Err(LoadError::Checksum { line: 1 })
+        ));
+        assert!(matches!(
+            parse(b"S9031000EC\nS107100070017202\n"),
+            Err(LoadError::Malformed { line: 2, .. })
+        ));
+        assert!(!is_srecord(&[0x4E, 0x71]));
+        for record in ["S!00", "S/00", "S\u{e9}00"] {
+            assert!(matches!(
+                parse(record.as_bytes()),
+                Err(LoadError::Malformed { line: 1, .. })
+            ));
+        }
+    }
+}
@@ -1,8 +1,7 @@
 use clap::Parser;
 use log::info;
-use phoenix::{Args, VM};
+use phoenix::{Args, StopReason, VM};
 use simplelog::ConfigBuilder;
-use std::fs;
 
 fn main() {
     let conf = ConfigBuilder::new()
@@ -16,9 +15,30 @@ fn main() {
     let args = Args::parse();
     info!("Starting VM");
     let mut vm = VM::new();
-    let pc_addr = u32::from_str_radix(&args.program_counter, 16).expect("Could not parse PC value");
-    vm.set_pc(pc_addr);
-    info!("PC set to {pc_addr:#X}");
+    info!("Loading program");
+    let images = args.images().unwrap_or_else(|e| {
+        eprintln!("Could not load: {e}");
+        std::process::exit(1);
+    });
+    for image in &images {
+        if let Err(e) = vm.load_image(image) {
+            eprintln!("Could not load: {e}");
+            std::process::exit(1);
+        }
+        info!("{} bytes loaded to RAM", image.size());
+    }
+    if let Some(pc) = args.program_counter {
+        let pc_addr = u32::from_str_radix(&pc, 16).expect("Could not parse PC value");
+        vm.set_pc(pc_addr);
+        info!("PC set to {pc_addr:#X}");
+    } else if images.iter().any(|image| image.entry.is_some()) {
+        info!("PC set to entry point {:#X}", vm.read_pc());
+    } else if let Err(e) = vm.reset() {
+        eprintln!("Reset failed: {e}");
+        std::process::exit(1);
+    } else {
+        info!("Booted with PC {:#X}", vm.read_pc());
+    }
     if let Some(ssp) = args.system_stack_pointer {
         let ssp = u32::from_str_radix(&ssp, 16).expect("Could not parse SSP value");
         vm.cpu.write_sp(ssp);
@@ -29,10 +49,15 @@ fn main() {
         vm.cpu.write_usp(usp);
         info!("USP set to {usp:#X}");
     }
-    info!("Loading program");
-    let rom = fs::read(&args.file)
-        .unwrap_or_else(|_| panic!("Could not open provided file {}", args.file));
-    vm.load(&rom);
-    info!("{} bytes loaded to RAM", rom.len());
-    vm.run();
+    let stop = vm.run();
+    info!("Stopped: {stop}");
+    // Nothing raises interrupts here, so a STOP will never be woken
+    if !matches!(stop.reason, StopReason::Halted | StopReason::Stopped) {
+        match vm.symbols.lookup(stop.pc) {
+            Some((name, 0)) => eprintln!("{stop} in {name}"),
+            Some((name, offset)) => eprintln!("{stop} in {name}+{offset:#X}"),
+            None => eprintln!("{stop}"),
+        }
+        std::process::exit(1);
+    }
 }
@@ -4,7 +4,7 @@ use std::{
     ops::{Add, BitOrAssign, Sub},
 };
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Size {
     Byte = 1,
     Word = 2,
@@ -19,6 +19,15 @@ impl Size {
             Self::Long => 32,
         }
     }
+
+    /// Bits of a 32 bit value that belong to an operand of this size
+    pub fn mask(&self) -> u32 {
+        match self {
+            Self::Byte => 0xFF,
+            Self::Word => 0xFFFF,
+            Self::Long => 0xFFFF_FFFF,
+        }
+    }
 }
 
 impl Display for Size {
@@ -206,11 +215,17 @@ pub enum AddressingMode {
     Extension(ExtensionMode),
 }
 
-impl AddressingMode {
-    pub fn from(inst: u16) -> Self {
+/// Instruction field holding a value the 68000 does not define
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InvalidEncoding;
+
+impl TryFrom<u16> for AddressingMode {
+    type Error = InvalidEncoding;
+
+    fn try_from(inst: u16) -> Result<Self, Self::Error> {
         let mode = (0b0000_0000_0011_1000 & inst) >> 3;
         let reg = 0b111 & inst;
-        match mode {
+        Ok(match mode {
             0b000 => Self::DataRegisterDirect(reg.try_into().unwrap()),
             0b001 => Self::AddressRegisterDirect(reg.try_into().unwrap()),
             0b010 => Self::AddressRegisterIndirect(reg.try_into().unwrap()),
@@ -224,10 +239,31 @@ impl AddressingMode {
                 0b010 => Self::Extension(ExtensionMode::PcRelativeDisplacement),
                 0b011 => Self::Extension(ExtensionMode::PcRelativeIndex),
                 0b100 => Self::Extension(ExtensionMode::Immediate),
-                _ => unreachable!(),
+                _ => return Err(InvalidEncoding),
             },
             _ => unreachable!(),
-        }
+        })
+    }
+}
+
+impl AddressingMode {
+    /// Operand is held in a register or the instruction stream rather than
+    /// memory, which costs long arithmetic operations 2 extra clock cycles
+    pub fn is_register_or_immediate(&self) -> bool {
+        matches!(
+            self,
+            Self::DataRegisterDirect(_)
+                | Self::AddressRegisterDirect(_)
+                | Self::Extension(ExtensionMode::Immediate)
+        )
+    }
+
+    /// Operand is addressed relative to PC and read from program space
+    pub fn is_pc_relative(&self) -> bool {
+        matches!(
+            self,
+            Self::Extension(ExtensionMode::PcRelativeDisplacement | ExtensionMode::PcRelativeIndex)
+        )
     }
 }
 
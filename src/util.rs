@@ -1,4 +1,4 @@
-use crate::types::Size;
+use crate::types::{InvalidEncoding, Size};
 
 #[allow(dead_code)]
 pub fn sign_extend_8_to_16(byte: u8) -> u16 {
@@ -50,6 +50,20 @@ pub(crate) fn is_carry(val1: u32, val2: u32, res: u32, size: Size) -> bool {
     }
 }
 
+/// Carry and overflow of `res = dst + src (+ X)`, valid with a carry in
+pub(crate) fn add_carry_overflow(dst: u32, src: u32, res: u32, size: Size) -> (bool, bool) {
+    let carry = (src & dst) | (!res & (src | dst));
+    let overflow = (src ^ res) & (dst ^ res);
+    (is_negative(carry, size), is_negative(overflow, size))
+}
+
+/// Borrow and overflow of `res = dst - src (- X)`, valid with a borrow in
+pub(crate) fn sub_carry_overflow(dst: u32, src: u32, res: u32, size: Size) -> (bool, bool) {
+    let carry = (src & !dst) | (res & !dst) | (src & res);
+    let overflow = (src ^ dst) & (res ^ dst);
+    (is_negative(carry, size), is_negative(overflow, size))
+}
+
 // TODO: this needs fixing
 pub(crate) fn is_overflow(val1: u32, val2: u32, res: u32, size: Size) -> bool {
     match size {
@@ -72,45 +86,60 @@ pub(crate) fn get_reg(inst: u16, idx: u8) -> u8 {
     get_bits(inst, idx, 3) as u8
 }
 
-pub(crate) fn get_size(inst: u16, idx: u8, coding: SizeCoding) -> Size {
-    match coding {
+pub(crate) fn get_size(inst: u16, idx: u8, coding: SizeCoding) -> Result<Size, InvalidEncoding> {
+    Ok(match coding {
         SizeCoding::Pink => match get_bits(inst, idx, 2) {
             0b00 => Size::Byte,
             0b01 => Size::Word,
             0b10 => Size::Long,
-            _ => unreachable!(),
+            _ => return Err(InvalidEncoding),
         },
         SizeCoding::Purple => match get_bits(inst, idx, 2) {
             0b01 => Size::Byte,
             0b11 => Size::Word,
             0b10 => Size::Long,
-            _ => unreachable!(),
+            _ => return Err(InvalidEncoding),
         },
-    }
+    })
 }
 
+/// Packed BCD representation of `byte`, which must be below 100
 #[allow(dead_code)]
-pub fn nibble_to_bcd(byte: u8) -> u8 {
-    match byte {
-        0b0000 => 0b0000, // 0
-        0b0001 => 0b0001, // 1
-        0b0010 => 0b0010, // 2
-        0b0011 => 0b0011, // 3
-        0b0100 => 0b0000, // 4
-        0b0101 => 0b0001, // 5
-        0b0110 => 0b0010, // 6
-        0b0111 => 0b0011, // 7
-        0b1000 => 0b1001, // 8
-        0b1001 => 0b1001, // 9
-        _ => unreachable!(),
-    }
+pub fn byte_to_packed_bcd(byte: u8) -> u8 {
+    debug_assert!(byte < 100);
+    ((byte / 10) << 4) | (byte % 10)
 }
 
-#[allow(dead_code)]
-pub fn byte_to_packed_bcd(byte: u8) -> u8 {
-    let lo = nibble_to_bcd(byte & 0xF);
-    let hi = nibble_to_bcd(byte >> 4);
-    hi + lo
+/// Decimal `dst + src + x` as performed by ABCD, returning the result, the
+/// decimal carry and the V flag. Invalid BCD digits are corrected the same
+/// way as on the 68000, and V is set when the correction flips bit 7 from 0
+/// to 1.
+pub fn bcd_add(dst: u8, src: u8, x: bool) -> (u8, bool, bool) {
+    let (dst, src) = (dst as u16, src as u16);
+    let sum = dst + src + x as u16;
+    // Binary carries out of bits 3 and 7
+    let bin_carry = ((dst & src) | (!sum & dst) | (!sum & src)) & 0x88;
+    // Digits above 9
+    let dec_carry = (((sum + 0x66) ^ sum) & 0x110) >> 1;
+    let carries = bin_carry | dec_carry;
+    let res = sum + carries - (carries >> 2);
+    let carry = (bin_carry | (sum & !res)) & 0x80 != 0;
+    let overflow = (!sum & res) & 0x80 != 0;
+    (res as u8, carry, overflow)
+}
+
+/// Decimal `dst - src - x` as performed by SBCD and NBCD, returning the
+/// result, the decimal borrow and the V flag. V is set when the correction
+/// flips bit 7 from 1 to 0.
+pub fn bcd_sub(dst: u8, src: u8, x: bool) -> (u8, bool, bool) {
+    let (dst, src) = (dst as u16, src as u16);
+    let diff = dst.wrapping_sub(src).wrapping_sub(x as u16);
+    // Binary borrows out of bits 3 and 7
+    let borrow = ((!dst & src) | (diff & !dst) | (diff & src)) & 0x88;
+    let res = diff.wrapping_sub(borrow - (borrow >> 2));
+    let carry = (borrow | (!diff & res)) & 0x80 != 0;
+    let overflow = (diff & !res) & 0x80 != 0;
+    (res as u8, carry, overflow)
 }
 
 pub enum SizeCoding {
@@ -122,7 +151,7 @@ pub enum SizeCoding {
 mod test {
     use crate::{
         types::Size,
-        util::{byte_to_packed_bcd, is_negative, is_overflow},
+        util::{bcd_add, bcd_sub, byte_to_packed_bcd, is_negative, is_overflow},
     };
 
     use super::{get_bits, is_bit_set, sign_extend_16_to_32, sign_transmute};
@@ -189,4 +218,24 @@ mod test {
         assert_eq!(byte_to_packed_bcd(35), 0b0011_0101);
         assert_eq!(byte_to_packed_bcd(95), 0b1001_0101);
     }
+
+    #[test]
+    fn test_bcd_add() {
+        assert_eq!(bcd_add(0x45, 0x38, false), (0x83, false, true));
+        assert_eq!(bcd_add(0x99, 0x01, false), (0x00, true, false));
+        assert_eq!(bcd_add(0x50, 0x50, true), (0x01, true, false));
+        // Invalid digits
+        assert_eq!(bcd_add(0x0F, 0x00, false), (0x15, false, false));
+        assert_eq!(bcd_add(0xFF, 0xFF, true), (0x65, true, false));
+    }
+
+    #[test]
+    fn test_bcd_sub() {
+        assert_eq!(bcd_sub(0x45, 0x38, false), (0x07, false, false));
+        assert_eq!(bcd_sub(0x00, 0x01, false), (0x99, true, false));
+        assert_eq!(bcd_sub(0x10, 0x01, true), (0x08, false, false));
+        // NBCD of 0 with X clear leaves 0 and no borrow
+        assert_eq!(bcd_sub(0x00, 0x00, false), (0x00, false, false));
+        assert_eq!(bcd_sub(0x00, 0x00, true), (0x99, true, false));
+    }
 }
@@ -1,23 +1,44 @@
 use std::fmt::Debug;
 
-use super::mmu::Mmu;
+use super::{
+    exception::{AccessFault, CpuResult, Exception, StepError, StopReason},
+    interrupt::InterruptAck,
+    mmu::{FunctionCode, Mmu},
+    recorder::{AccessKind, Transaction},
+};
 use crate::{
     types::{ConditionCode, Size, Value},
     util::sign_transmute,
     vm::StatusRegister as SR,
 };
 
-pub struct Cpu<'a> {
+#[derive(Clone)]
+pub struct Cpu {
     sr: u16,
     pc: usize,
     pub(crate) data_registers: [u32; 8],
     pub(crate) addr_registers: [u32; 7],
     usp: u32,
     ssp: u32,
-    pub mmu: Mmu<'a>,
+    prefetch: [u16; 2],
+    prefetched: usize,
+    pub(crate) ir: u16,
+    pub(crate) inst_pc: u32,
+    pub(crate) halted: bool,
+    pub(crate) stopped: bool,
+    pub(crate) trace_pending: bool,
+    /// Stop with [`StopReason::IllegalEncoding`] instead of taking the
+    /// illegal instruction exception
+    pub stop_on_illegal: bool,
+    pub(crate) ipl: u8,
+    pub(crate) nmi_pending: bool,
+    pub(crate) iack: InterruptAck,
+    pub(crate) cycles: u64,
+    pub(crate) recorder: Option<Vec<Transaction>>,
+    pub mmu: Mmu,
 }
 
-impl<'a> Debug for Cpu<'a> {
+impl Debug for Cpu {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Cpu")
             .field("sr", &self.sr)
@@ -26,12 +47,25 @@ impl<'a> Debug for Cpu<'a> {
             .field("addr_registers", &self.addr_registers)
             .field("usp", &self.usp)
             .field("ssp", &self.ssp)
+            .field("prefetch", &self.prefetch_queue())
+            .field("ir", &self.ir)
+            .field("halted", &self.halted)
+            .field("stopped", &self.stopped)
+            .field("ipl", &self.ipl)
+            .field("cycles", &self.cycles)
             .finish()
     }
 }
 
-impl<'a> Default for Cpu<'a> {
+impl Default for Cpu {
     fn default() -> Self {
+        Self::with_mmu(Mmu::default())
+    }
+}
+
+impl Cpu {
+    /// CPU attached to the address space `mmu`
+    pub fn with_mmu(mmu: Mmu) -> Self {
         Self {
             sr: 0x2000,
             pc: Default::default(),
@@ -39,80 +73,353 @@ impl<'a> Default for Cpu<'a> {
             addr_registers: Default::default(),
             usp: 0x00FF0000,
             ssp: 0x01000000,
-            mmu: Default::default(),
+            prefetch: Default::default(),
+            prefetched: 0,
+            ir: Default::default(),
+            inst_pc: Default::default(),
+            halted: false,
+            stopped: false,
+            trace_pending: false,
+            stop_on_illegal: false,
+            ipl: 0,
+            nmi_pending: false,
+            iack: Default::default(),
+            cycles: 0,
+            recorder: None,
+            mmu,
         }
     }
 }
 
-impl<'a> Cpu<'a> {
+const BUS_CYCLE: u64 = 4;
+const TAS_CYCLE: u64 = 10;
+
+impl Cpu {
     pub const STACK: u8 = 7;
-    pub fn run(&mut self) {
+    /// Execute until an instruction stops the CPU
+    pub fn run(&mut self) -> StepError {
         loop {
-            let inst = self.fetch_word();
-            self.exec(inst);
+            if let Err(e) = self.step() {
+                return e;
+            }
+        }
+    }
+
+    /// Execute a single instruction, including any exception processing it
+    /// triggers
+    pub fn step(&mut self) -> Result<(), StepError> {
+        if self.halted {
+            return Err(self.step_error(StopReason::DoubleFault));
+        }
+        if self.stopped {
+            if !self.interrupt_pending() {
+                return Err(self.step_error(StopReason::Stopped));
+            }
+            self.stopped = false;
+        }
+        self.inst_pc = self.read_pc();
+        let res = if self.interrupt_pending() {
+            self.interrupt()
+        } else {
+            self.trace_pending = self.is_trace_mode();
+            self.fetch_word().and_then(|inst| {
+                self.ir = inst;
+                self.exec(inst)
+            })
+        }
+        .and_then(|_| self.fill_prefetch());
+        match res {
+            Ok(()) => Ok(()),
+            Err(e) => self.handle_exception(e),
         }
+        .and_then(|_| self.trace())
+        .map_err(|reason| self.step_error(reason))
     }
 
-    pub fn step(&mut self) {
-        let inst = self.fetch_word();
-        self.exec(inst);
+    pub(crate) fn step_error(&self, reason: StopReason) -> StepError {
+        StepError {
+            reason,
+            pc: self.inst_pc,
+            opcode: self.ir,
+        }
     }
 
-    pub fn load(&mut self, buffer: &[u8]) {
-        self.mmu.load(buffer);
+    pub fn load(&mut self, buffer: &[u8]) -> Result<(), u32> {
+        self.mmu.load(0, buffer)
     }
 
-    pub fn fetch_word(&mut self) -> u16 {
+    /// Take the next word from the prefetch queue, reading it from memory
+    /// if the queue has run dry
+    pub fn fetch_word(&mut self) -> CpuResult<u16> {
         self.pc += 2;
-        self.mmu.read_word(self.pc as u32 - 2)
+        if self.prefetched > 0 {
+            Ok(self.pop_prefetch())
+        } else {
+            self.read_program_word(self.pc as u32 - 2)
+        }
+    }
+
+    fn pop_prefetch(&mut self) -> u16 {
+        let word = self.prefetch[0];
+        self.prefetch[0] = self.prefetch[1];
+        self.prefetched -= 1;
+        word
+    }
+
+    pub(crate) fn fill_prefetch(&mut self) -> CpuResult {
+        while self.prefetched < 2 {
+            let addr = self.read_pc().wrapping_add(2 * self.prefetched as u32);
+            self.prefetch[self.prefetched] = self.read_program_word(addr)?;
+            self.prefetched += 1;
+        }
+        Ok(())
+    }
+
+    /// Words at PC and PC + 2 as held in the prefetch queue, words not yet
+    /// fetched read as zero
+    pub fn prefetch_queue(&self) -> [u16; 2] {
+        let mut queue = [0; 2];
+        queue[..self.prefetched].copy_from_slice(&self.prefetch[..self.prefetched]);
+        queue
+    }
+
+    /// Load the prefetch queue with the words at PC and PC + 2, without
+    /// touching memory
+    pub fn set_prefetch_queue(&mut self, queue: [u16; 2]) {
+        self.prefetch = queue;
+        self.prefetched = 2;
     }
 
     pub fn peep_word(&self) -> u16 {
         self.mmu.read_word(self.pc as u32)
     }
 
-    pub fn fetch_signed_word(&mut self) -> i16 {
-        self.pc += 2;
-        sign_transmute(self.mmu.read_word(self.pc as u32 - 2))
+    pub fn fetch_signed_word(&mut self) -> CpuResult<i16> {
+        Ok(sign_transmute(self.fetch_word()?))
     }
 
-    pub fn fetch_long(&mut self) -> u32 {
-        self.pc += 4;
-        self.mmu.read_long(self.pc as u32 - 4)
+    pub fn fetch_long(&mut self) -> CpuResult<u32> {
+        let high = self.fetch_word()? as u32;
+        let low = self.fetch_word()? as u32;
+        Ok((high << 16) + low)
+    }
+
+    /// Immediate operand of `size` from the instruction stream, bytes take
+    /// the low half of a word
+    pub fn fetch_immediate(&mut self, size: Size) -> CpuResult<u32> {
+        Ok(match size {
+            Size::Byte => self.fetch_word()? as u32 & 0xFF,
+            Size::Word => self.fetch_word()? as u32,
+            Size::Long => self.fetch_long()?,
+        })
     }
 
     pub fn peep_long(&self) -> u32 {
         self.mmu.read_long(self.pc as u32)
     }
 
-    pub fn push_long(&mut self, val: u32) {
-        let new = self.read_sp() - 4;
+    pub fn push_long(&mut self, val: u32) -> CpuResult {
+        let new = self.read_sp().wrapping_sub(4);
         self.write_sp(new);
-        self.mmu.write_long(new, val);
+        self.write_long_descending(new, val)
     }
 
-    pub fn pop_long(&mut self) -> u32 {
+    pub fn pop_long(&mut self) -> CpuResult<u32> {
         let pc = self.read_sp();
-        self.write_sp(pc + 4);
-        self.mmu.read_long(pc)
+        self.write_sp(pc.wrapping_add(4));
+        self.read_long(pc)
     }
 
-    pub fn push_word(&mut self, val: u16) {
-        let new = self.read_sp() - 2;
+    pub fn push_word(&mut self, val: u16) -> CpuResult {
+        let new = self.read_sp().wrapping_sub(2);
         self.write_sp(new);
-        self.mmu.write_word(new, val);
+        self.write_word(new, val)
     }
 
-    pub fn pop_word(&mut self) -> u16 {
+    pub fn pop_word(&mut self) -> CpuResult<u16> {
         let pc = self.read_sp();
-        self.write_sp(pc + 4);
-        self.mmu.read_word(pc)
+        self.write_sp(pc.wrapping_add(2));
+        self.read_word(pc)
+    }
+
+    pub fn read_byte(&mut self, addr: u32) -> CpuResult<u8> {
+        self.read_byte_in(addr, false)
+    }
+
+    pub fn read_word(&mut self, addr: u32) -> CpuResult<u16> {
+        self.read_word_in(addr, false)
+    }
+
+    /// Long reads are two word bus cycles, high word first
+    pub fn read_long(&mut self, addr: u32) -> CpuResult<u32> {
+        self.read_long_in(addr, false)
+    }
+
+    pub(crate) fn read_byte_in(&mut self, addr: u32, program: bool) -> CpuResult<u8> {
+        let fc = self.function_code(program);
+        Ok(self.bus_read(addr, Size::Byte, fc)? as u8)
+    }
+
+    pub(crate) fn read_word_in(&mut self, addr: u32, program: bool) -> CpuResult<u16> {
+        let fc = self.function_code(program);
+        self.check_alignment(addr, true, fc)?;
+        self.bus_read(addr, Size::Word, fc)
+    }
+
+    pub(crate) fn read_long_in(&mut self, addr: u32, program: bool) -> CpuResult<u32> {
+        let fc = self.function_code(program);
+        self.check_alignment(addr, true, fc)?;
+        let high = self.bus_read(addr, Size::Word, fc)? as u32;
+        let low = self.bus_read(addr.wrapping_add(2), Size::Word, fc)? as u32;
+        Ok((high << 16) | low)
+    }
+
+    pub fn write_byte(&mut self, addr: u32, val: u8) -> CpuResult {
+        let fc = self.function_code(false);
+        self.bus_write(addr, Size::Byte, val.into(), fc)
+    }
+
+    pub fn write_word(&mut self, addr: u32, val: u16) -> CpuResult {
+        let fc = self.function_code(false);
+        self.check_alignment(addr, false, fc)?;
+        self.bus_write(addr, Size::Word, val, fc)
+    }
+
+    /// Long writes are two word bus cycles, high word first
+    pub fn write_long(&mut self, addr: u32, val: u32) -> CpuResult {
+        let fc = self.function_code(false);
+        self.check_alignment(addr, false, fc)?;
+        self.bus_write(addr, Size::Word, (val >> 16) as u16, fc)?;
+        self.bus_write(addr.wrapping_add(2), Size::Word, val as u16, fc)
+    }
+
+    /// Long write with the low word first, as done by predecrement modes and
+    /// stack pushes
+    pub fn write_long_descending(&mut self, addr: u32, val: u32) -> CpuResult {
+        let fc = self.function_code(false);
+        self.check_alignment(addr, false, fc)?;
+        self.bus_write(addr.wrapping_add(2), Size::Word, val as u16, fc)?;
+        self.bus_write(addr, Size::Word, (val >> 16) as u16, fc)
+    }
+
+    fn read_program_word(&mut self, addr: u32) -> CpuResult<u16> {
+        self.read_word_in(addr, true)
+    }
+
+    pub(crate) fn test_and_set(&mut self, addr: u32) -> CpuResult<u8> {
+        let fc = self.function_code(false);
+        let val = self
+            .mmu
+            .bus_read(addr, Size::Byte, fc)
+            .map_err(|_| bus_error(addr, true, fc))? as u8;
+        self.mmu
+            .bus_write(addr, Size::Byte, (val | 0x80).into(), fc)
+            .map_err(|_| bus_error(addr, false, fc))?;
+        self.bus_cycle(AccessKind::Tas, TAS_CYCLE, fc, addr, Size::Byte, val.into());
+        Ok(val)
+    }
+
+    fn bus_read(&mut self, addr: u32, size: Size, fc: FunctionCode) -> CpuResult<u16> {
+        let val = self
+            .mmu
+            .bus_read(addr, size, fc)
+            .map_err(|_| bus_error(addr, true, fc))?;
+        self.bus_cycle(AccessKind::Read, BUS_CYCLE, fc, addr, size, val);
+        Ok(val)
+    }
+
+    fn bus_write(&mut self, addr: u32, size: Size, val: u16, fc: FunctionCode) -> CpuResult {
+        self.mmu
+            .bus_write(addr, size, val, fc)
+            .map_err(|_| bus_error(addr, false, fc))?;
+        self.bus_cycle(AccessKind::Write, BUS_CYCLE, fc, addr, size, val);
+        Ok(())
+    }
+
+    pub(crate) fn iack_cycle(&mut self, level: u8, vector: u8) {
+        let addr = 0xFFFFF1 | (u32::from(level) << 1);
+        let fc = FunctionCode::InterruptAck;
+        self.bus_cycle(
+            AccessKind::Read,
+            BUS_CYCLE,
+            fc,
+            addr,
+            Size::Byte,
+            vector.into(),
+        );
+    }
+
+    fn bus_cycle(
+        &mut self,
+        kind: AccessKind,
+        cycles: u64,
+        fc: FunctionCode,
+        addr: u32,
+        size: Size,
+        value: u16,
+    ) {
+        self.cycles += cycles;
+        if self.recorder.is_some() {
+            self.record(Transaction::Access {
+                kind,
+                cycles,
+                function_code: fc.into(),
+                address: addr & 0xFFFFFF,
+                size,
+                value,
+            });
+        }
+    }
+
+    pub(crate) fn idle(&mut self, n: u64) {
+        self.cycles += n;
+        if n > 0 {
+            self.record(Transaction::Idle(n));
+        }
+    }
+
+    pub(crate) fn idle_after_prefetch(&mut self, n: u64) -> CpuResult {
+        self.fill_prefetch()?;
+        self.idle(n);
+        Ok(())
+    }
+
+    /// Clock cycles elapsed since power on
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    fn check_alignment(&self, addr: u32, read: bool, fc: FunctionCode) -> CpuResult {
+        if addr.is_multiple_of(2) {
+            return Ok(());
+        }
+        Err(Exception::AddressError(AccessFault {
+            address: addr & 0xFFFFFF,
+            read,
+            exception: false,
+            function_code: fc,
+        }))
+    }
+
+    /// Function code posted on FC0-FC2 for a program or data access in the
+    /// current mode
+    pub fn function_code(&self, program: bool) -> FunctionCode {
+        FunctionCode::new(self.is_supervisor_mode(), program)
     }
 
     pub fn is_supervisor_mode(&self) -> bool {
         (self.sr & 0b0010_0000_0000_0000) == 0b0010_0000_0000_0000
     }
 
+    /// Whether STOP is waiting for an interrupt
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    pub fn is_trace_mode(&self) -> bool {
+        (self.sr & 0b1000_0000_0000_0000) == 0b1000_0000_0000_0000
+    }
+
     pub fn decrement_dr(&mut self, reg: u8, by: u32) {
         let reg = usize::from(reg);
         self.data_registers[reg] = self.data_registers[reg].wrapping_sub(by)
@@ -127,12 +434,13 @@ impl<'a> Cpu<'a> {
         assert!(reg < 8, "Indexing into non-existant Address Register");
         if reg == 7 {
             if self.is_supervisor_mode() {
-                self.ssp -= by;
+                self.ssp = self.ssp.wrapping_sub(by);
             } else {
-                self.usp -= by;
+                self.usp = self.usp.wrapping_sub(by);
             }
         } else {
-            self.addr_registers[usize::from(reg)] -= by;
+            self.addr_registers[usize::from(reg)] =
+                self.addr_registers[usize::from(reg)].wrapping_sub(by);
         }
     }
 
@@ -140,12 +448,13 @@ impl<'a> Cpu<'a> {
         assert!(reg < 8, "Indexing into non-existant Address Register");
         if reg == 7 {
             if self.is_supervisor_mode() {
-                self.ssp += by;
+                self.ssp = self.ssp.wrapping_add(by);
             } else {
-                self.usp += by;
+                self.usp = self.usp.wrapping_add(by);
             }
         } else {
-            self.addr_registers[usize::from(reg)] += by;
+            self.addr_registers[usize::from(reg)] =
+                self.addr_registers[usize::from(reg)].wrapping_add(by);
         }
     }
 
@@ -215,16 +524,27 @@ impl<'a> Cpu<'a> {
         self.pc.try_into().unwrap()
     }
 
+    /// Jump to `pc`, discarding the prefetch queue
     pub fn write_pc(&mut self, pc: u32) {
         self.pc = pc.try_into().unwrap();
+        self.prefetched = 0;
     }
 
     pub fn decrement_pc(&mut self, by: usize) {
         self.pc -= by;
+        self.prefetched = 0;
     }
 
+    /// Skip `by` bytes of the instruction stream without a bus cycle for the
+    /// words already in the prefetch queue
     pub fn increment_pc(&mut self, by: usize) {
         self.pc += by;
+        for _ in 0..by / 2 {
+            if self.prefetched == 0 {
+                break;
+            }
+            self.pop_prefetch();
+        }
     }
 
     pub fn write_sp(&mut self, val: u32) {
@@ -271,15 +591,6 @@ impl<'a> Cpu<'a> {
         self.usp = val;
     }
 
-    pub fn trap_vec(&mut self, addr: u32) {
-        let mut sr = self.read_sr();
-        sr |= 0b0010_0000_0000_0000;
-        self.write_sr(sr);
-        self.push_long(self.read_pc());
-        self.push_word(sr);
-        self.write_pc(addr);
-    }
-
     pub fn test_cc(&self, cc: ConditionCode) -> bool {
         match cc {
             ConditionCode::True => true,
@@ -324,6 +635,15 @@ pub enum StatusRegister {
     X = 16,
 }
 
+fn bus_error(addr: u32, read: bool, fc: FunctionCode) -> Exception {
+    Exception::BusError(AccessFault {
+        address: addr & 0xFFFFFF,
+        read,
+        exception: false,
+        function_code: fc,
+    })
+}
+
 #[cfg(test)]
 mod test_sr_bitlogic {
     use super::{Cpu, StatusRegister as SR};
@@ -357,10 +677,133 @@ mod test_stack {
     fn test_long_stack() {
         let mut cpu = Cpu::default();
         cpu.write_sp(0xFFF0);
-        cpu.push_long(0xFAFABABA);
-        cpu.push_long(0xABBA1050);
-        assert_eq!(cpu.pop_long(), 0xABBA1050);
-        assert_eq!(cpu.pop_long(), 0xFAFABABA);
+        cpu.push_long(0xFAFABABA).unwrap();
+        cpu.push_long(0xABBA1050).unwrap();
+        assert_eq!(cpu.pop_long().unwrap(), 0xABBA1050);
+        assert_eq!(cpu.pop_long().unwrap(), 0xFAFABABA);
+    }
+
+    #[test]
+    fn test_word_stack() {
+        let mut cpu = Cpu::default();
+        cpu.write_sp(0xFFF0);
+        cpu.push_word(0xBABA).unwrap();
+        cpu.push_long(0xABBA1050).unwrap();
+        assert_eq!(cpu.pop_long().unwrap(), 0xABBA1050);
+        assert_eq!(cpu.pop_word().unwrap(), 0xBABA);
+        assert_eq!(cpu.read_sp(), 0xFFF0);
+    }
+}
+
+#[cfg(test)]
+mod test_cycles {
+    use super::Cpu;
+    use crate::Vector;
+
+    fn cycles(words: &[u16]) -> u64 {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_long(Vector::Trap as u32, 0x4000);
+        for (i, w) in words.iter().enumerate() {
+            cpu.mmu.write_word(0x1000 + 2 * i as u32, *w);
+        }
+        cpu.write_ar(0, 0x2000);
+        cpu.write_ssp(0x3000);
+        cpu.write_pc(0x1000);
+        cpu.fill_prefetch().unwrap();
+        let start = cpu.cycles();
+        cpu.step().unwrap();
+        cpu.cycles() - start
+    }
+
+    #[test]
+    fn test_move() {
+        assert_eq!(cycles(&[0x3200]), 4); // MOVE.w D0, D1
+        assert_eq!(cycles(&[0x2010]), 12); // MOVE.l (A0), D0
+        assert_eq!(cycles(&[0x3020]), 10); // MOVE.w -(A0), D0
+        assert_eq!(cycles(&[0x3080]), 8); // MOVE.w D0, (A0)
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(cycles(&[0xD280]), 8); // ADD.l D0, D1
+        assert_eq!(cycles(&[0xD290]), 14); // ADD.l (A0), D1
+        assert_eq!(cycles(&[0xD0C0]), 8); // ADDA.w D0, A0
+        assert_eq!(cycles(&[0x5280]), 8); // ADDQ.l #1, D0
+        assert_eq!(cycles(&[0x4280]), 6); // CLR.l D0
+        assert_eq!(cycles(&[0xC300]), 6); // ABCD D0, D1
+        assert_eq!(cycles(&[0xC308]), 18); // ABCD -(A0), -(A1)
+        assert_eq!(cycles(&[0x4810]), 12); // NBCD (A0)
+        assert_eq!(cycles(&[0x0340]), 6); // BCHG D1, D0
+        assert_eq!(cycles(&[0x0880, 31]), 14); // BCLR #31, D0
+        assert_eq!(cycles(&[0x01D0]), 12); // BSET D0, (A0)
+        assert_eq!(cycles(&[0x0810, 2]), 12); // BTST #2, (A0)
+        assert_eq!(cycles(&[0xE380]), 10); // ASL.l #1, D0
+        assert_eq!(cycles(&[0xE048]), 22); // LSR.w #8, D0
+        assert_eq!(cycles(&[0xE5D0]), 12); // ROXL.w (A0)
+        assert_eq!(cycles(&[0xC0C1]), 38); // MULU.w D1, D0
+        assert_eq!(cycles(&[0xB308]), 12); // CMPM.b (A0)+, (A1)+
+        assert_eq!(cycles(&[0xB181]), 8); // EOR.l D0, D1
+        assert_eq!(cycles(&[0xD388]), 30); // ADDX.l -(A0), -(A1)
+        assert_eq!(cycles(&[0x4AC0]), 4); // TAS D0
+        assert_eq!(cycles(&[0x4AD0]), 14); // TAS (A0)
+        assert_eq!(cycles(&[0x50C0]), 6); // ST D0
+        assert_eq!(cycles(&[0x51C0]), 4); // SF D0
+        assert_eq!(cycles(&[0x50D0]), 12); // ST (A0)
+    }
+
+    #[test]
+    fn test_flow() {
+        assert_eq!(cycles(&[0x4E71]), 4); // NOP
+        assert_eq!(cycles(&[0x6002]), 10); // BRA.b
+        assert_eq!(cycles(&[0x6702]), 8); // BEQ.b not taken
+        assert_eq!(cycles(&[0x4E90]), 16); // JSR (A0)
+        assert_eq!(cycles(&[0x41F0, 0x0000]), 12); // LEA (0,A0,D0.w), A0
+        assert_eq!(cycles(&[0x4E40]), 34); // TRAP #0
+    }
+}
+
+#[cfg(test)]
+mod test_prefetch {
+    use super::Cpu;
+
+    #[test]
+    fn test_queue() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0x4E71); // NOP
+        cpu.mmu.write_word(0x1002, 0x4E71); // NOP
+        cpu.mmu.write_word(0x1004, 0x1234);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x1002);
+        assert_eq!(cpu.prefetch_queue(), [0x4E71, 0x1234]);
+    }
+
+    #[test]
+    fn test_stale_queue() {
+        let mut cpu = Cpu::default();
+        cpu.write_pc(0x1000);
+        cpu.set_prefetch_queue([0x7001, 0x7002]); // MOVEQ #1, D0; MOVEQ #2, D0
+
+        // Overwriting memory does not affect words already prefetched
+        cpu.mmu.write_word(0x1000, 0x7003);
+        cpu.mmu.write_word(0x1002, 0x7004);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_dr(0), 1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_dr(0), 2);
+        assert_eq!(cpu.prefetch_queue(), [0xFFFF, 0xFFFF]);
+    }
+
+    #[test]
+    fn test_jump_refills() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x2000, 0x4E71);
+        cpu.mmu.write_word(0x2002, 0x4E75);
+        cpu.write_pc(0x1000);
+        cpu.set_prefetch_queue([0x4EF8, 0x2000]); // JMP (0x2000).w
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x2000);
+        assert_eq!(cpu.prefetch_queue(), [0x4E71, 0x4E75]);
     }
 }
 
@@ -404,8 +847,9 @@ mod test_ea_long {
             usp: 0,
             ssp: 0,
             mmu: Mmu::from_vec(MEM.to_vec()),
+            ..Default::default()
         };
-        let ea = cpu.read_ea_long(AddressRegisterDirect(3));
+        let ea = cpu.read_ea_long(AddressRegisterDirect(3)).unwrap();
         assert_eq!(ea, (0x33123456));
     }
 
@@ -419,8 +863,9 @@ mod test_ea_long {
             usp: 0,
             ssp: 0,
             mmu: Mmu::from_vec(MEM.to_vec()),
+            ..Default::default()
         };
-        let ea = cpu.read_ea_long(DataRegisterDirect(5));
+        let ea = cpu.read_ea_long(DataRegisterDirect(5)).unwrap();
         assert_eq!(ea, (0xD5333333));
     }
 
@@ -434,8 +879,9 @@ mod test_ea_long {
             usp: 0,
             ssp: 0,
             mmu: Mmu::from_vec(MEM.to_vec()),
+            ..Default::default()
         };
-        let ea = cpu.read_ea_long(AddressRegisterIndirect(2));
+        let ea = cpu.read_ea_long(AddressRegisterIndirect(2)).unwrap();
         assert_eq!(ea, 0x3321837A);
     }
 
@@ -449,8 +895,11 @@ mod test_ea_long {
             usp: 0,
             ssp: 0,
             mmu: Mmu::from_vec(MEM.to_vec()),
+            ..Default::default()
         };
-        let ea = cpu.read_ea_long(AddressRegisterIndirectPostIncrement(2));
+        let ea = cpu
+            .read_ea_long(AddressRegisterIndirectPostIncrement(2))
+            .unwrap();
         assert_eq!(ea, 0x3321837A);
         assert_eq!(cpu.addr_registers[2], 0x00000054);
     }
@@ -465,8 +914,11 @@ mod test_ea_long {
             usp: 0,
             ssp: 0,
             mmu: Mmu::from_vec(MEM.to_vec()),
+            ..Default::default()
         };
-        let ea = cpu.read_ea_long(AddressRegisterIndirectPreDecrement(2));
+        let ea = cpu
+            .read_ea_long(AddressRegisterIndirectPreDecrement(2))
+            .unwrap();
         assert_eq!(ea, 0x055689E9);
         assert_eq!(cpu.addr_registers[2], 0x0000004C);
     }
@@ -481,8 +933,11 @@ mod test_ea_long {
             usp: 0,
             ssp: 0,
             mmu: Mmu::from_vec(MEM.to_vec()),
+            ..Default::default()
         };
-        let ea = cpu.read_ea_long(AddressRegisterIndirectDisplacement(2));
+        let ea = cpu
+            .read_ea_long(AddressRegisterIndirectDisplacement(2))
+            .unwrap();
         assert_eq!(ea, 0xDE63FCC4);
     }
 
@@ -496,8 +951,9 @@ mod test_ea_long {
             usp: 0,
             ssp: 0,
             mmu: Mmu::from_vec(MEM.to_vec()),
+            ..Default::default()
         };
-        let ea = cpu.read_ea_long(AddressRegisterIndirectIndex(2));
+        let ea = cpu.read_ea_long(AddressRegisterIndirectIndex(2)).unwrap();
         assert_eq!(ea, 0xFDBCD6FA);
     }
 
@@ -511,8 +967,9 @@ mod test_ea_long {
             usp: 0,
             ssp: 0,
             mmu: Mmu::from_vec(MEM.to_vec()),
+            ..Default::default()
         };
-        let ea = cpu.read_ea_long(Extension(PcRelativeDisplacement));
+        let ea = cpu.read_ea_long(Extension(PcRelativeDisplacement)).unwrap();
         assert_eq!(ea, 0xD07215AB);
     }
 
@@ -526,8 +983,9 @@ mod test_ea_long {
             usp: 0,
             ssp: 0,
             mmu: Mmu::from_vec(MEM.to_vec()),
+            ..Default::default()
         };
-        let ea = cpu.read_ea_long(Extension(PcRelativeIndex));
+        let ea = cpu.read_ea_long(Extension(PcRelativeIndex)).unwrap();
         assert_eq!(ea, 0xD07215AB);
     }
 
@@ -541,8 +999,9 @@ mod test_ea_long {
             usp: 0,
             ssp: 0,
             mmu: Mmu::from_vec(MEM.to_vec()),
+            ..Default::default()
         };
-        let ea = cpu.read_ea_word(Extension(Word));
+        let ea = cpu.read_ea_word(Extension(Word)).unwrap();
         assert_eq!(ea, 0xDC16);
     }
 
@@ -556,8 +1015,9 @@ mod test_ea_long {
             usp: 0,
             ssp: 0,
             mmu: Mmu::from_vec(MEM.to_vec()),
+            ..Default::default()
         };
-        let ea = cpu.read_ea_long(Extension(Long));
+        let ea = cpu.read_ea_long(Extension(Long)).unwrap();
         assert_eq!(ea, 0xDC1651A9);
     }
 
@@ -571,8 +1031,9 @@ mod test_ea_long {
             usp: 0,
             ssp: 0,
             mmu: Mmu::from_vec(MEM.to_vec()),
+            ..Default::default()
         };
-        let ea = cpu.read_ea_long(Extension(Immediate));
+        let ea = cpu.read_ea_long(Extension(Immediate)).unwrap();
         assert_eq!(ea, 0x00000088);
     }
 }
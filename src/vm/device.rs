@@ -0,0 +1,182 @@
+use super::mmu::FunctionCode;
+use crate::types::Size;
+
+/// Bus cycle not acknowledged by any device, ending in a bus error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BusError;
+
+/// Memory or peripheral mapped into a region of the address space. Offsets
+/// are relative to the start of the region the device is mapped at.
+/// Devices are cloned along with the VM when it is snapshotted and must be
+/// able to move between threads with it.
+pub trait Device: CloneDevice + Send {
+    /// Byte or word read by the CPU
+    fn read(&mut self, offset: u32, size: Size, fc: FunctionCode) -> Result<u16, BusError>;
+
+    /// Byte or word write by the CPU
+    fn write(
+        &mut self,
+        offset: u32,
+        size: Size,
+        val: u16,
+        fc: FunctionCode,
+    ) -> Result<(), BusError>;
+
+    /// Byte as seen by a debugger, without any side effects on the device
+    fn peek(&self, offset: u32) -> u8;
+
+    /// Store a byte on behalf of a loader or debugger
+    fn poke(&mut self, offset: u32, val: u8);
+
+    /// Bytes of storage behind the device, if it cannot be mapped over a
+    /// larger region
+    fn size(&self) -> Option<usize> {
+        None
+    }
+
+    /// Reset line asserted, by a hardware reset or the RESET instruction
+    fn reset(&mut self) {}
+}
+
+/// Boxed copy of a device, implemented for every `Device` that is `Clone`
+pub trait CloneDevice {
+    fn clone_device(&self) -> Box<dyn Device>;
+}
+
+impl<T: Device + Clone + 'static> CloneDevice for T {
+    fn clone_device(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+}
+
+/// Plain read/write memory
+#[derive(Debug, Clone)]
+pub struct Ram {
+    data: Vec<u8>,
+}
+
+impl Ram {
+    /// `len` bytes of memory, filled with 0xFF as uninitialised memory reads
+    pub fn new(len: usize) -> Self {
+        Self {
+            data: vec![0xFF; len],
+        }
+    }
+
+    pub fn from_vec(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+impl Device for Ram {
+    fn read(&mut self, offset: u32, size: Size, _fc: FunctionCode) -> Result<u16, BusError> {
+        Ok(read_bytes(&self.data, offset, size))
+    }
+
+    fn write(
+        &mut self,
+        offset: u32,
+        size: Size,
+        val: u16,
+        _fc: FunctionCode,
+    ) -> Result<(), BusError> {
+        let offset = offset as usize;
+        match size {
+            Size::Byte => self.data[offset] = val as u8,
+            _ => self.data[offset..offset + 2].copy_from_slice(&val.to_be_bytes()),
+        }
+        Ok(())
+    }
+
+    fn peek(&self, offset: u32) -> u8 {
+        self.data[offset as usize]
+    }
+
+    fn poke(&mut self, offset: u32, val: u8) {
+        self.data[offset as usize] = val;
+    }
+
+    fn size(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+/// Read only memory. CPU writes are ignored, or end in a bus error if the
+/// ROM is set to fault on writes. Loaders can still store into it.
+#[derive(Debug, Clone)]
+pub struct Rom {
+    data: Vec<u8>,
+    fault_on_write: bool,
+}
+
+impl Rom {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            fault_on_write: false,
+        }
+    }
+
+    /// Make CPU writes end in a bus error instead of being ignored
+    pub fn fault_on_write(mut self, fault: bool) -> Self {
+        self.fault_on_write = fault;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+impl Device for Rom {
+    fn read(&mut self, offset: u32, size: Size, _fc: FunctionCode) -> Result<u16, BusError> {
+        Ok(read_bytes(&self.data, offset, size))
+    }
+
+    fn write(
+        &mut self,
+        _offset: u32,
+        _size: Size,
+        _val: u16,
+        _fc: FunctionCode,
+    ) -> Result<(), BusError> {
+        if self.fault_on_write {
+            Err(BusError)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn peek(&self, offset: u32) -> u8 {
+        self.data[offset as usize]
+    }
+
+    fn poke(&mut self, offset: u32, val: u8) {
+        self.data[offset as usize] = val;
+    }
+
+    fn size(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+/// Big endian byte or word at `offset` of `data`
+fn read_bytes(data: &[u8], offset: u32, size: Size) -> u16 {
+    let offset = offset as usize;
+    match size {
+        Size::Byte => data[offset].into(),
+        _ => u16::from_be_bytes([data[offset], data[offset + 1]]),
+    }
+}
@@ -1,56 +1,135 @@
-use super::cpu::Cpu;
+use super::{
+    cpu::Cpu,
+    exception::{CpuResult, Exception},
+};
 use crate::types::{AddressingMode, ExtensionMode, Size, Value};
 use crate::util::{sign_extend_16_to_32, sign_extend_8_to_32};
 
-impl<'a> Cpu<'a> {
-    pub fn get_ea(&mut self, ea: AddressingMode) -> u32 {
+/// Bytes an address register moves by for a (An)+ or -(An) access of `size`.
+/// Byte accesses through A7 step by 2 to keep the stack aligned.
+pub(crate) fn address_step(reg: u8, size: Size) -> u32 {
+    match size {
+        Size::Byte if reg == 7 => 2,
+        Size::Byte => 1,
+        Size::Word => 2,
+        Size::Long => 4,
+    }
+}
+
+/// Operand of a read-modify-write instruction with its effective address
+/// already calculated
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Location {
+    DataRegister(u8),
+    Memory(u32),
+}
+
+impl Cpu {
+    pub fn get_ea(&mut self, ea: AddressingMode) -> CpuResult<u32> {
         let val = match ea {
             AddressingMode::AddressRegisterIndirect(r) => self.read_ar(r),
             AddressingMode::AddressRegisterIndirectDisplacement(r) => {
-                let displacement = self.fetch_signed_word();
+                let displacement = self.fetch_signed_word()?;
                 let val = self.read_ar(r);
-                (val as i64 + displacement as i64) as u32
+                val.wrapping_add(displacement as u32)
             }
             AddressingMode::AddressRegisterIndirectIndex(reg) => {
-                let exword = self.fetch_word();
+                let exword = self.fetch_word()?;
                 let ar = self.read_ar(reg);
                 let offset = self.get_index_offset(exword);
-                ar + offset
+                self.idle(2);
+                ar.wrapping_add(offset)
             }
             AddressingMode::Extension(e) => match e {
-                ExtensionMode::Word => self.fetch_word() as u32,
-                ExtensionMode::Long => self.fetch_long(),
+                ExtensionMode::Word => self.fetch_word()? as u32,
+                ExtensionMode::Long => self.fetch_long()?,
                 ExtensionMode::PcRelativeDisplacement => {
                     let pc = self.read_pc();
-                    let offset = sign_extend_16_to_32(self.fetch_word());
-                    (pc as i32 + offset as i32) as u32
+                    let offset = sign_extend_16_to_32(self.fetch_word()?);
+                    pc.wrapping_add(offset)
                 }
                 ExtensionMode::PcRelativeIndex => {
                     let pc = self.read_pc();
-                    let exword = self.fetch_word();
+                    let exword = self.fetch_word()?;
                     let offset = self.get_index_offset(exword);
+                    self.idle(2);
                     offset.wrapping_add(pc)
                 }
-                ExtensionMode::Immediate => unreachable!(),
+                ExtensionMode::Immediate => return Err(Exception::Illegal),
             },
-            _ => unreachable!(),
+            _ => return Err(Exception::Illegal),
         };
-        val & 0xFFFFFF
+        Ok(val & 0xFFFFFF)
+    }
+
+    /// Address of a memory operand of `size`, applying any increment or
+    /// decrement exactly once so it can be both read and written
+    pub(crate) fn resolve_ea(&mut self, ea: AddressingMode, size: Size) -> CpuResult<u32> {
+        let step = |reg: u8| address_step(reg, size);
+        match ea {
+            AddressingMode::AddressRegisterIndirectPostIncrement(reg) => {
+                let addr = self.read_ar(reg);
+                self.increment_ar(reg, step(reg));
+                Ok(addr)
+            }
+            AddressingMode::AddressRegisterIndirectPreDecrement(reg) => {
+                self.decrement_ar(reg, step(reg));
+                self.idle(2);
+                Ok(self.read_ar(reg))
+            }
+            _ => self.get_ea(ea),
+        }
+    }
+
+    /// Calculate the location of a data alterable operand, rejecting address
+    /// registers, PC relative and immediate modes
+    pub(crate) fn locate(&mut self, ea: AddressingMode, size: Size) -> CpuResult<Location> {
+        match ea {
+            AddressingMode::DataRegisterDirect(reg) => Ok(Location::DataRegister(reg)),
+            AddressingMode::AddressRegisterDirect(_)
+            | AddressingMode::Extension(
+                ExtensionMode::PcRelativeDisplacement
+                | ExtensionMode::PcRelativeIndex
+                | ExtensionMode::Immediate,
+            ) => Err(Exception::Illegal),
+            _ => Ok(Location::Memory(self.resolve_ea(ea, size)?)),
+        }
+    }
+
+    pub(crate) fn read_location(&mut self, loc: Location, size: Size) -> CpuResult<u32> {
+        match (loc, size) {
+            (Location::DataRegister(reg), _) => Ok(self.read_dr_sized(reg, size).into()),
+            (Location::Memory(addr), Size::Byte) => Ok(self.read_byte(addr)?.into()),
+            (Location::Memory(addr), Size::Word) => Ok(self.read_word(addr)?.into()),
+            (Location::Memory(addr), Size::Long) => self.read_long(addr),
+        }
     }
 
-    pub fn read_ea(&mut self, ea: AddressingMode, size: Size) -> Value {
+    pub(crate) fn write_location(&mut self, loc: Location, size: Size, val: u32) -> CpuResult {
+        match (loc, size) {
+            (Location::DataRegister(reg), _) => {
+                self.write_dr(reg, size, val);
+                Ok(())
+            }
+            (Location::Memory(addr), Size::Byte) => self.write_byte(addr, val as u8),
+            (Location::Memory(addr), Size::Word) => self.write_word(addr, val as u16),
+            (Location::Memory(addr), Size::Long) => self.write_long(addr, val),
+        }
+    }
+
+    pub fn read_ea(&mut self, ea: AddressingMode, size: Size) -> CpuResult<Value> {
         use Value::*;
         if let AddressingMode::AddressRegisterDirect(_) = ea {
-            return Long(self.read_ea_long(ea));
-        }
-        match size {
-            Size::Byte => Byte(self.read_ea_byte(ea)),
-            Size::Word => Word(self.read_ea_word(ea)),
-            Size::Long => Long(self.read_ea_long(ea)),
+            return Ok(Long(self.read_ea_long(ea)?));
         }
+        Ok(match size {
+            Size::Byte => Byte(self.read_ea_byte(ea)?),
+            Size::Word => Word(self.read_ea_word(ea)?),
+            Size::Long => Long(self.read_ea_long(ea)?),
+        })
     }
 
-    pub fn write_ea(&mut self, ea: AddressingMode, size: Size, val: Value) {
+    pub fn write_ea(&mut self, ea: AddressingMode, size: Size, val: Value) -> CpuResult {
         if let AddressingMode::AddressRegisterDirect(_) = ea {
             return self.write_ea_long(ea, val.into());
         }
@@ -61,67 +140,70 @@ impl<'a> Cpu<'a> {
         }
     }
 
-    pub fn read_ea_byte(&mut self, ea: AddressingMode) -> u8 {
+    pub fn read_ea_byte(&mut self, ea: AddressingMode) -> CpuResult<u8> {
         use AddressingMode::*;
-        match ea {
+        Ok(match ea {
             DataRegisterDirect(reg) => self.read_dr(reg) as u8,
             AddressRegisterDirect(reg) => self.read_ar(reg) as u8,
             AddressRegisterIndirect(reg) => {
                 assert!(reg < 8);
-                self.mmu.read_byte(self.read_ar(reg))
+                self.read_byte(self.read_ar(reg))?
             }
             AddressRegisterIndirectPostIncrement(reg) => {
                 let addr = self.read_ar(reg);
                 self.increment_ar(reg, 1);
-                self.mmu.read_byte(addr)
+                self.read_byte(addr)?
             }
             AddressRegisterIndirectPreDecrement(reg) => {
                 self.decrement_ar(reg, 1);
-                self.mmu.read_byte(self.read_ar(reg))
+                self.idle(2);
+                self.read_byte(self.read_ar(reg))?
             }
             AddressRegisterIndirectDisplacement(reg) => {
                 assert!(reg < 8);
-                let displacement = self.fetch_signed_word();
-                let target = (self.read_ar(reg) as i32 + displacement as i32) as u32;
-                self.mmu.read_byte(target)
+                let displacement = self.fetch_signed_word()?;
+                let target = self.read_ar(reg).wrapping_add(displacement as u32);
+                self.read_byte(target)?
             }
             AddressRegisterIndirectIndex(reg) => {
                 assert!(reg < 8);
-                let exword = self.fetch_word();
+                let exword = self.fetch_word()?;
                 let ar = self.read_ar(reg);
                 let offset = self.get_index_offset(exword);
-                let addr = ar + offset;
-                self.mmu.read_byte(addr)
+                self.idle(2);
+                let addr = ar.wrapping_add(offset);
+                self.read_byte(addr)?
             }
             Extension(ext) => match ext {
                 ExtensionMode::Word => {
                     // TODO: should only fetch first or last 32KiB of RAM
-                    let addr = self.fetch_word();
-                    self.mmu.read_byte(addr.into())
+                    let addr = self.fetch_word()?;
+                    self.read_byte(addr.into())?
                 }
                 ExtensionMode::Long => {
-                    let addr = self.fetch_long();
-                    self.mmu.read_byte(addr)
+                    let addr = self.fetch_long()?;
+                    self.read_byte(addr)?
                 }
                 ExtensionMode::PcRelativeDisplacement => {
                     let pc = self.read_pc();
-                    let offset = sign_extend_16_to_32(self.fetch_word());
-                    let target = (pc as i32 + offset as i32) as u32;
-                    self.mmu.read_byte(target)
+                    let offset = sign_extend_16_to_32(self.fetch_word()?);
+                    let target = pc.wrapping_add(offset);
+                    self.read_byte_in(target, true)?
                 }
                 ExtensionMode::PcRelativeIndex => {
                     let pc = self.read_pc();
-                    let exword = self.fetch_word();
+                    let exword = self.fetch_word()?;
                     let offset = self.get_index_offset(exword);
-                    let addr = offset.wrapping_add(pc + 2);
-                    self.mmu.read_byte(addr)
+                    self.idle(2);
+                    let addr = offset.wrapping_add(pc.wrapping_add(2));
+                    self.read_byte_in(addr, true)?
                 }
-                ExtensionMode::Immediate => self.fetch_word() as u8,
+                ExtensionMode::Immediate => self.fetch_word()? as u8,
             },
-        }
+        })
     }
 
-    pub fn write_ea_byte(&mut self, ea: AddressingMode, val: u8) {
+    pub fn write_ea_byte(&mut self, ea: AddressingMode, val: u8) -> CpuResult {
         match ea {
             AddressingMode::DataRegisterDirect(reg) => {
                 let val = (self.read_dr(reg) & 0xFFFFFF00) + val as u32;
@@ -129,106 +211,111 @@ impl<'a> Cpu<'a> {
             }
             AddressingMode::AddressRegisterIndirect(reg) => {
                 assert!(reg < 8);
-                self.mmu.write_byte(self.read_ar(reg), val);
+                self.write_byte(self.read_ar(reg), val)?;
             }
             AddressingMode::AddressRegisterIndirectPostIncrement(reg) => {
-                self.mmu.write_byte(self.read_ar(reg), val);
+                self.write_byte(self.read_ar(reg), val)?;
                 self.increment_ar(reg, 1);
             }
             AddressingMode::AddressRegisterIndirectPreDecrement(reg) => {
                 self.decrement_ar(reg, 1);
-                self.mmu.write_byte(self.read_ar(reg), val);
+                self.write_byte(self.read_ar(reg), val)?;
             }
             AddressingMode::AddressRegisterIndirectDisplacement(reg) => {
                 assert!(reg < 8);
-                let displacement = self.fetch_signed_word();
-                let target = (self.read_ar(reg) as i32 + displacement as i32) as u32;
-                self.mmu.write_byte(target, val);
+                let displacement = self.fetch_signed_word()?;
+                let target = self.read_ar(reg).wrapping_add(displacement as u32);
+                self.write_byte(target, val)?;
             }
             AddressingMode::AddressRegisterIndirectIndex(reg) => {
-                let exword = self.fetch_word();
+                let exword = self.fetch_word()?;
                 let ar = self.read_ar(reg);
                 let offset = self.get_index_offset(exword);
-                let addr = ar + offset;
-                self.mmu.write_byte(addr, val)
+                self.idle(2);
+                let addr = ar.wrapping_add(offset);
+                self.write_byte(addr, val)?
             }
             AddressingMode::Extension(e) => match e {
                 ExtensionMode::Word => {
-                    let addr = self.fetch_word();
-                    self.mmu.write_byte(addr.into(), val);
+                    let addr = self.fetch_word()?;
+                    self.write_byte(addr.into(), val)?;
                 }
                 ExtensionMode::Long => {
-                    let addr = self.fetch_long();
-                    self.mmu.write_byte(addr, val)
+                    let addr = self.fetch_long()?;
+                    self.write_byte(addr, val)?
                 }
-                _ => panic!("Unable to write with this Addressing Mode"),
+                _ => return Err(Exception::Illegal),
             },
-            _ => panic!("Unable to write with this Addressing Mode"),
+            _ => return Err(Exception::Illegal),
         }
+        Ok(())
     }
 
-    pub fn read_ea_word(&mut self, ea: AddressingMode) -> u16 {
+    pub fn read_ea_word(&mut self, ea: AddressingMode) -> CpuResult<u16> {
         use AddressingMode::*;
-        match ea {
+        Ok(match ea {
             DataRegisterDirect(reg) => self.read_dr(reg) as u16,
             AddressRegisterDirect(reg) => self.read_ar(reg) as u16,
             AddressRegisterIndirect(reg) => {
                 assert!(reg < 8);
-                self.mmu.read_word(self.read_ar(reg))
+                self.read_word(self.read_ar(reg))?
             }
             AddressRegisterIndirectPostIncrement(reg) => {
                 let addr = self.read_ar(reg);
                 self.increment_ar(reg, 2);
-                self.mmu.read_word(addr)
+                self.read_word(addr)?
             }
             AddressRegisterIndirectPreDecrement(reg) => {
                 self.decrement_ar(reg, 2);
-                self.mmu.read_word(self.read_ar(reg))
+                self.idle(2);
+                self.read_word(self.read_ar(reg))?
             }
             AddressRegisterIndirectDisplacement(reg) => {
                 assert!(reg < 8);
                 assert!(reg < 8);
-                let displacement = self.fetch_signed_word();
-                let target = (self.read_ar(reg) as i32 + displacement as i32) as u32;
-                self.mmu.read_word(target)
+                let displacement = self.fetch_signed_word()?;
+                let target = self.read_ar(reg).wrapping_add(displacement as u32);
+                self.read_word(target)?
             }
             AddressRegisterIndirectIndex(reg) => {
                 assert!(reg < 8);
-                let exword = self.fetch_word();
+                let exword = self.fetch_word()?;
                 let ar = self.read_ar(reg);
                 let offset = self.get_index_offset(exword);
-                let addr = ar + offset;
-                self.mmu.read_word(addr)
+                self.idle(2);
+                let addr = ar.wrapping_add(offset);
+                self.read_word(addr)?
             }
             Extension(ext) => match ext {
                 ExtensionMode::Word => {
                     // TODO: should only fetch first or last 32KiB of RAM
-                    let addr = self.fetch_word();
-                    self.mmu.read_word(addr.into())
+                    let addr = self.fetch_word()?;
+                    self.read_word(addr.into())?
                 }
                 ExtensionMode::Long => {
-                    let addr = self.fetch_long();
-                    self.mmu.read_word(addr)
+                    let addr = self.fetch_long()?;
+                    self.read_word(addr)?
                 }
                 ExtensionMode::PcRelativeDisplacement => {
                     let pc = self.read_pc();
-                    let offset = sign_extend_16_to_32(self.fetch_word());
-                    let target = (pc as i32 + offset as i32) as u32;
-                    self.mmu.read_word(target)
+                    let offset = sign_extend_16_to_32(self.fetch_word()?);
+                    let target = pc.wrapping_add(offset);
+                    self.read_word_in(target, true)?
                 }
                 ExtensionMode::PcRelativeIndex => {
                     let pc = self.read_pc();
-                    let exword = self.fetch_word();
+                    let exword = self.fetch_word()?;
                     let offset = self.get_index_offset(exword);
-                    let addr = offset.wrapping_add(pc + 2);
-                    self.mmu.read_word(addr)
+                    self.idle(2);
+                    let addr = offset.wrapping_add(pc.wrapping_add(2));
+                    self.read_word_in(addr, true)?
                 }
-                ExtensionMode::Immediate => self.fetch_word(),
+                ExtensionMode::Immediate => self.fetch_word()?,
             },
-        }
+        })
     }
 
-    pub fn write_ea_word(&mut self, ea: AddressingMode, val: u16) {
+    pub fn write_ea_word(&mut self, ea: AddressingMode, val: u16) -> CpuResult {
         match ea {
             AddressingMode::DataRegisterDirect(reg) => {
                 let val = (self.read_dr(reg) & 0xFFFF0000) + val as u32;
@@ -240,144 +327,173 @@ impl<'a> Cpu<'a> {
             }
             AddressingMode::AddressRegisterIndirect(reg) => {
                 assert!(reg < 8);
-                self.mmu.write_word(self.read_ar(reg), val);
+                self.write_word(self.read_ar(reg), val)?;
             }
             AddressingMode::AddressRegisterIndirectPostIncrement(reg) => {
-                self.mmu.write_word(self.read_ar(reg), val);
+                self.write_word(self.read_ar(reg), val)?;
                 self.increment_ar(reg, 2);
             }
             AddressingMode::AddressRegisterIndirectPreDecrement(reg) => {
                 self.decrement_ar(reg, 2);
-                self.mmu.write_word(self.read_ar(reg), val);
+                self.write_word(self.read_ar(reg), val)?;
             }
             AddressingMode::AddressRegisterIndirectDisplacement(reg) => {
                 assert!(reg < 8);
-                let displacement = self.fetch_signed_word();
-                let target = (self.read_ar(reg) as i32 + displacement as i32) as u32;
-                self.mmu.write_word(target, val);
+                let displacement = self.fetch_signed_word()?;
+                let target = self.read_ar(reg).wrapping_add(displacement as u32);
+                self.write_word(target, val)?;
             }
             AddressingMode::AddressRegisterIndirectIndex(reg) => {
-                let exword = self.fetch_word();
+                let exword = self.fetch_word()?;
                 let ar = self.read_ar(reg);
                 let offset = self.get_index_offset(exword);
-                let addr = ar + offset;
-                self.mmu.write_word(addr, val)
+                self.idle(2);
+                let addr = ar.wrapping_add(offset);
+                self.write_word(addr, val)?
             }
             AddressingMode::Extension(e) => match e {
                 ExtensionMode::Word => {
-                    let addr = self.fetch_word();
-                    self.mmu.write_word(addr.into(), val);
+                    let addr = self.fetch_word()?;
+                    self.write_word(addr.into(), val)?;
                 }
                 ExtensionMode::Long => {
-                    let addr = self.fetch_long();
-                    self.mmu.write_word(addr, val)
+                    let addr = self.fetch_long()?;
+                    self.write_word(addr, val)?
                 }
-                _ => panic!("Unable to write with this Addressing Mode"),
+                _ => return Err(Exception::Illegal),
             },
         }
+        Ok(())
     }
 
-    pub fn read_ea_long(&mut self, ea: AddressingMode) -> u32 {
+    pub fn read_ea_long(&mut self, ea: AddressingMode) -> CpuResult<u32> {
         use AddressingMode::*;
-        match ea {
+        Ok(match ea {
             DataRegisterDirect(reg) => self.read_dr(reg),
             AddressRegisterDirect(reg) => self.read_ar(reg),
             AddressRegisterIndirect(reg) => {
                 assert!(reg < 8);
-                self.mmu.read_long(self.read_ar(reg))
+                self.read_long(self.read_ar(reg))?
             }
             AddressRegisterIndirectPostIncrement(reg) => {
                 let addr = self.read_ar(reg);
                 self.increment_ar(reg, 4);
-                self.mmu.read_long(addr)
+                self.read_long(addr)?
             }
             AddressRegisterIndirectPreDecrement(reg) => {
                 assert!(reg < 8);
                 self.decrement_ar(reg, 4);
-                self.mmu.read_long(self.read_ar(reg))
+                self.idle(2);
+                self.read_long(self.read_ar(reg))?
             }
             AddressRegisterIndirectDisplacement(reg) => {
                 assert!(reg < 8);
-                let displacement = self.fetch_signed_word();
-                let target = (self.read_ar(reg) as i32 + displacement as i32) as u32;
-                self.mmu.read_long(target)
+                let displacement = self.fetch_signed_word()?;
+                let target = self.read_ar(reg).wrapping_add(displacement as u32);
+                self.read_long(target)?
             }
             AddressRegisterIndirectIndex(reg) => {
                 assert!(reg < 8);
-                let exword = self.fetch_word();
+                let exword = self.fetch_word()?;
                 let ar = self.read_ar(reg);
                 let offset = self.get_index_offset(exword);
-                let addr = ar + offset;
-                self.mmu.read_long(addr)
+                self.idle(2);
+                let addr = ar.wrapping_add(offset);
+                self.read_long(addr)?
             }
             Extension(ext) => match ext {
                 ExtensionMode::Word => {
-                    let addr = self.fetch_word();
-                    self.mmu.read_long(addr.into())
+                    let addr = self.fetch_word()?;
+                    self.read_long(addr.into())?
                 }
                 ExtensionMode::Long => {
-                    let addr = self.fetch_long();
-                    self.mmu.read_long(addr)
+                    let addr = self.fetch_long()?;
+                    self.read_long(addr)?
                 }
                 ExtensionMode::PcRelativeDisplacement => {
                     let pc = self.read_pc();
-                    let offset = sign_extend_16_to_32(self.fetch_word());
-                    let target = offset.wrapping_add(pc + 2);
-                    self.mmu.read_long(target)
+                    let offset = sign_extend_16_to_32(self.fetch_word()?);
+                    let target = offset.wrapping_add(pc.wrapping_add(2));
+                    self.read_long_in(target, true)?
                 }
                 ExtensionMode::PcRelativeIndex => {
                     let pc = self.read_pc();
-                    let exword = self.fetch_word();
+                    let exword = self.fetch_word()?;
                     let offset = self.get_index_offset(exword);
-                    let addr = offset.wrapping_add(pc + 2);
-                    self.mmu.read_long(addr)
+                    self.idle(2);
+                    let addr = offset.wrapping_add(pc.wrapping_add(2));
+                    self.read_long_in(addr, true)?
                 }
-                ExtensionMode::Immediate => self.fetch_long(),
+                ExtensionMode::Immediate => self.fetch_long()?,
             },
-        }
+        })
     }
 
-    pub fn write_ea_long(&mut self, ea: AddressingMode, val: u32) {
+    pub fn write_ea_long(&mut self, ea: AddressingMode, val: u32) -> CpuResult {
         match ea {
             AddressingMode::DataRegisterDirect(reg) => self.write_dr(reg, Size::Long, val),
             AddressingMode::AddressRegisterDirect(reg) => self.write_ar(reg, val),
             AddressingMode::AddressRegisterIndirect(reg) => {
                 assert!(reg < 8);
-                self.mmu.write_long(self.read_ar(reg), val);
+                self.write_long(self.read_ar(reg), val)?;
             }
             AddressingMode::AddressRegisterIndirectPostIncrement(reg) => {
-                self.mmu.write_long(self.read_ar(reg), val);
+                self.write_long(self.read_ar(reg), val)?;
                 self.increment_ar(reg, 4);
             }
             AddressingMode::AddressRegisterIndirectPreDecrement(reg) => {
                 self.decrement_ar(reg, 4);
-                self.mmu.write_long(self.read_ar(reg), val);
+                self.write_long_descending(self.read_ar(reg), val)?;
             }
             AddressingMode::AddressRegisterIndirectDisplacement(reg) => {
                 assert!(reg < 8);
-                let displacement = self.fetch_signed_word();
-                let target = (self.read_ar(reg) as i32 + displacement as i32) as u32;
-                self.mmu.write_long(target, val);
+                let displacement = self.fetch_signed_word()?;
+                let target = self.read_ar(reg).wrapping_add(displacement as u32);
+                self.write_long(target, val)?;
             }
             AddressingMode::AddressRegisterIndirectIndex(reg) => {
-                let exword = self.fetch_word();
+                let exword = self.fetch_word()?;
                 let ar = self.read_ar(reg);
                 let offset = self.get_index_offset(exword);
-                let addr = ar + offset;
-                self.mmu.write_long(addr, val)
+                self.idle(2);
+                let addr = ar.wrapping_add(offset);
+                self.write_long(addr, val)?
             }
             AddressingMode::Extension(e) => match e {
                 ExtensionMode::Word => {
-                    let addr = self.fetch_word();
-                    self.mmu.write_long(addr.into(), val);
+                    let addr = self.fetch_word()?;
+                    self.write_long(addr.into(), val)?;
                 }
                 ExtensionMode::Long => {
-                    let addr = self.fetch_long();
-                    self.mmu.write_long(addr, val)
+                    let addr = self.fetch_long()?;
+                    self.write_long(addr, val)?
                 }
-                _ => panic!("Unable to write with this Addressing Mode"),
+                _ => return Err(Exception::Illegal),
             },
         }
+        Ok(())
+    }
+
+    /// Clock cycles LEA and PEA spend calculating `ea` beyond reading
+    /// extension words
+    pub(crate) fn address_calc_idle(ea: AddressingMode) -> u64 {
+        match ea {
+            AddressingMode::AddressRegisterIndirectIndex(_)
+            | AddressingMode::Extension(ExtensionMode::PcRelativeIndex) => 2,
+            _ => 0,
+        }
+    }
+
+    /// Clock cycles JMP and JSR spend calculating `ea` and refilling the
+    /// prefetch queue beyond reading extension words
+    pub(crate) fn jump_idle(ea: AddressingMode) -> u64 {
+        match ea {
+            AddressingMode::AddressRegisterIndirect(_) => 0,
+            AddressingMode::AddressRegisterIndirectIndex(_)
+            | AddressingMode::Extension(ExtensionMode::PcRelativeIndex) => 4,
+            AddressingMode::Extension(ExtensionMode::Long) => 0,
+            _ => 2,
+        }
     }
 
     /// Extension Bit Format
@@ -387,6 +503,7 @@ impl<'a> Cpu<'a> {
     /// A: Xi
     /// B: Xi size - 0 for word, 1 for long
     /// C: 8 bit signed displacement
+    /// Adding the index takes 2 clock cycles, which callers account for.
     fn get_index_offset(&self, word: u16) -> u32 {
         let displacement = sign_extend_8_to_32((word & 0b0000_0000_1111_1111) as u8);
         let reg = ((word & 0b0111_0000_0000_0000) >> 12) as u8;
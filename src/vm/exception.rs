@@ -0,0 +1,520 @@
+use std::fmt::Display;
+
+use log::{error, trace};
+
+use super::{cpu::Cpu, mmu::FunctionCode};
+use crate::{types::InvalidEncoding, Vector};
+
+/// Details of the bus cycle that caused a bus or address error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AccessFault {
+    pub address: u32,
+    pub read: bool,
+    /// Access was made while processing an exception rather than an instruction
+    pub exception: bool,
+    pub function_code: FunctionCode,
+}
+
+impl AccessFault {
+    /// Special status word
+    ///  |F E D C B A 9 8 7 6 5|4|3|2 1 0|
+    ///  |---------------------|R|I|F-F-F|
+    /// R: 1 for read, 0 for write
+    /// I: 1 if not processing an instruction
+    /// F: Function code
+    fn status_word(&self) -> u16 {
+        ((self.read as u16) << 4)
+            | ((self.exception as u16) << 3)
+            | u16::from(u8::from(self.function_code))
+    }
+}
+
+/// Reason an instruction was aborted before completing
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Exception {
+    BusError(AccessFault),
+    AddressError(AccessFault),
+    /// Opcode or operand encoding not defined by the 68000
+    Illegal,
+    /// Valid instruction that the emulator does not support yet
+    Unimplemented,
+    /// Execution stopped by the guest
+    Halt,
+}
+
+impl From<InvalidEncoding> for Exception {
+    fn from(_: InvalidEncoding) -> Self {
+        Self::Illegal
+    }
+}
+
+pub type CpuResult<T = ()> = Result<T, Exception>;
+
+/// Reason execution stopped
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// Instruction is valid but not supported by the emulator
+    Unimplemented,
+    /// Opcode is not a valid 68000 instruction, only reported when
+    /// [`Cpu::stop_on_illegal`] is set
+    IllegalEncoding,
+    /// Guest requested a halt
+    Halted,
+    /// PC reached a breakpoint
+    Breakpoint,
+    /// Bus or address error while processing a group 0 exception
+    DoubleFault,
+    /// STOP is waiting for an interrupt above the mask
+    Stopped,
+}
+
+impl Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unimplemented => write!(f, "unimplemented instruction"),
+            Self::IllegalEncoding => write!(f, "illegal instruction"),
+            Self::Halted => write!(f, "halted"),
+            Self::Breakpoint => write!(f, "breakpoint"),
+            Self::DoubleFault => write!(f, "double bus fault"),
+            Self::Stopped => write!(f, "stopped"),
+        }
+    }
+}
+
+/// Execution stop with the location of the instruction that caused it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StepError {
+    pub reason: StopReason,
+    pub pc: u32,
+    pub opcode: u16,
+}
+
+impl Display for StepError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at {:#010X} (opcode {:#06X})",
+            self.reason, self.pc, self.opcode
+        )
+    }
+}
+
+impl std::error::Error for StepError {}
+
+impl Cpu {
+    /// Clock cycles of group 1 and 2 exception processing not spent on
+    /// stacking the frame and fetching the vector
+    pub(super) const EXCEPTION_IDLE: u64 = 6;
+    /// Clock cycles of group 0 exception processing not spent on stacking
+    /// the frame and fetching the vector
+    const GROUP_0_IDLE: u64 = 10;
+    /// Clock cycles of reset exception processing not spent fetching the
+    /// vectors and filling the prefetch queue
+    const RESET_IDLE: u64 = 16;
+
+    /// Process an exception that aborted the current instruction
+    pub(crate) fn handle_exception(&mut self, exception: Exception) -> Result<(), StopReason> {
+        let res = match exception {
+            Exception::BusError(fault) => self.access_error(Vector::BusError as u32, fault),
+            Exception::AddressError(fault) => self.access_error(Vector::AddressError as u32, fault),
+            Exception::Illegal if self.stop_on_illegal => {
+                self.write_pc(self.inst_pc);
+                return Err(StopReason::IllegalEncoding);
+            }
+            Exception::Illegal => self.illegal_instruction(),
+            Exception::Unimplemented => {
+                self.write_pc(self.inst_pc);
+                return Err(StopReason::Unimplemented);
+            }
+            Exception::Halt => return Err(StopReason::Halted),
+        };
+        match res {
+            Ok(()) => Ok(()),
+            Err(Exception::BusError(_) | Exception::AddressError(_)) => {
+                error!("Double bus fault at {:#X}", self.inst_pc);
+                self.halted = true;
+                Err(StopReason::DoubleFault)
+            }
+            Err(e) => self.handle_exception(e),
+        }
+    }
+
+    /// Group 1 and 2 exception processing through the vector at `vector`,
+    /// stacking the 3 word frame of SR and the current PC.
+    pub fn exception(&mut self, vector: u32) -> CpuResult {
+        let pc = self.read_pc();
+        self.exception_with_pc(vector, pc)
+    }
+
+    /// Group 1 exception for an instruction that does not complete, stacking
+    /// the address of the offending instruction.
+    pub fn abort_instruction(&mut self, vector: u32) -> CpuResult {
+        self.trace_pending = false;
+        let pc = self.inst_pc;
+        self.exception_with_pc(vector, pc)
+    }
+
+    /// Group 0 exception processing, stacking the 7 word frame of the fault
+    /// status, access address, instruction register, SR and PC.
+    pub fn access_error(&mut self, vector: u32, fault: AccessFault) -> CpuResult {
+        trace!("Exception {vector:#X} accessing {:#X}", fault.address);
+        self.trace_pending = false;
+        self.idle(Self::GROUP_0_IDLE);
+        let sr = self.enter_supervisor();
+        self.push_long(self.read_pc())?;
+        self.push_word(sr)?;
+        self.push_word(self.ir)?;
+        self.push_long(fault.address)?;
+        self.push_word(fault.status_word())?;
+        self.jump_vector(vector)
+    }
+
+    /// Reset exception processing, as after power on or when the RESET input
+    /// is asserted: enter supervisor mode with tracing off and all interrupts
+    /// masked, then load SSP from address 0 and PC from address 4. The
+    /// vectors are read from program space, through the ROM overlay if one is
+    /// set. A bus or address error while doing so halts the CPU.
+    pub fn reset_exception(&mut self) -> Result<(), StepError> {
+        trace!("Reset");
+        self.halted = false;
+        self.stopped = false;
+        self.trace_pending = false;
+        self.nmi_pending = false;
+        self.write_sr(0x2700);
+        self.idle(Self::RESET_IDLE);
+        self.mmu.set_booting(true);
+        let vectors = self
+            .read_long_in(Vector::ResetSP as u32, true)
+            .and_then(|ssp| Ok((ssp, self.read_long_in(Vector::ResetPC as u32, true)?)));
+        self.mmu.set_booting(false);
+        let res = vectors.and_then(|(ssp, pc)| {
+            self.write_ssp(ssp);
+            self.write_pc(pc);
+            self.fill_prefetch()
+        });
+        res.map_err(|_| {
+            error!("Double bus fault during reset");
+            self.halted = true;
+            self.step_error(StopReason::DoubleFault)
+        })
+    }
+
+    /// Trace exception after an instruction that started with T set. An
+    /// instruction that raised a TRAP, TRAPV, CHK or divide by zero exception
+    /// is traced with the handler address as the stacked PC.
+    pub(super) fn trace(&mut self) -> Result<(), StopReason> {
+        if !self.trace_pending {
+            return Ok(());
+        }
+        self.trace_pending = false;
+        self.stopped = false;
+        match self.exception(Vector::Trace as u32) {
+            Ok(()) => Ok(()),
+            Err(e) => self.handle_exception(e),
+        }
+    }
+
+    fn exception_with_pc(&mut self, vector: u32, pc: u32) -> CpuResult {
+        trace!("Exception {vector:#X}");
+        let sr = self.enter_supervisor();
+        self.push_frame(vector, pc, sr)
+    }
+
+    /// Stack the 3 word frame of `sr` and `pc` and jump through `vector`
+    pub(super) fn push_frame(&mut self, vector: u32, pc: u32, sr: u16) -> CpuResult {
+        self.idle(Self::EXCEPTION_IDLE);
+        let res = self
+            .write_frame(pc, sr)
+            .and_then(|_| self.jump_vector(vector));
+        let during_exception = |fault| AccessFault {
+            exception: true,
+            ..fault
+        };
+        res.map_err(|e| match e {
+            Exception::BusError(fault) => Exception::BusError(during_exception(fault)),
+            Exception::AddressError(fault) => Exception::AddressError(during_exception(fault)),
+            e => e,
+        })
+    }
+
+    /// Write a short PC/SR frame in the order the 68000 does: PC low word,
+    /// then SR, then PC high word
+    fn write_frame(&mut self, pc: u32, sr: u16) -> CpuResult {
+        let sp = self.read_sp().wrapping_sub(6);
+        self.write_sp(sp);
+        self.write_word(sp.wrapping_add(4), pc as u16)?;
+        self.write_word(sp, sr)?;
+        self.write_word(sp.wrapping_add(2), (pc >> 16) as u16)
+    }
+
+    /// Switch to supervisor mode with tracing disabled, returning the old SR
+    pub(super) fn enter_supervisor(&mut self) -> u16 {
+        let sr = self.read_sr();
+        self.write_sr((sr | 0b0010_0000_0000_0000) & !0b1000_0000_0000_0000);
+        sr
+    }
+
+    fn jump_vector(&mut self, vector: u32) -> CpuResult {
+        let addr = self.read_long(vector & 0x3FC)?;
+        self.write_pc(addr);
+        self.fill_prefetch()
+    }
+
+    pub(crate) fn privilege_violation(&mut self) -> CpuResult {
+        self.abort_instruction(Vector::PrivilegeViolation as u32)
+    }
+
+    /// Illegal instruction exception, using the line 1010 and line 1111
+    /// emulator vectors for the unassigned opcode lines
+    fn illegal_instruction(&mut self) -> CpuResult {
+        let vector = match self.ir >> 12 {
+            0xA => Vector::UnimplementedA,
+            0xF => Vector::UnimplementedF,
+            _ => Vector::IllegalInstruction,
+        };
+        self.abort_instruction(vector as u32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AccessFault, StopReason};
+    use crate::{
+        vm::{
+            cpu::Cpu,
+            device::{Ram, Rom},
+            mmu::{FunctionCode, Mmu},
+        },
+        Vector,
+    };
+
+    #[test]
+    fn test_trap_frame() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_long(Vector::Trap as u32, 0x4000);
+        cpu.write_sr(0x0004);
+        cpu.write_ssp(0x2000);
+        cpu.write_pc(0x1002);
+        cpu.exception(Vector::Trap as u32).unwrap();
+        assert_eq!(cpu.read_pc(), 0x4000);
+        assert_eq!(cpu.read_sr(), 0x2004);
+        assert_eq!(cpu.read_ssp(), 0x1FFA);
+        assert_eq!(cpu.mmu.read_word(0x1FFA), 0x0004);
+        assert_eq!(cpu.mmu.read_long(0x1FFC), 0x1002);
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_long(Vector::Trace as u32, 0x4000);
+        cpu.mmu.write_long(Vector::Trap as u32, 0x5000);
+        cpu.mmu
+            .write_long(Vector::IllegalInstruction as u32, 0x6000);
+        cpu.mmu.write_word(0x1000, 0x4E71); // NOP
+        cpu.mmu.write_word(0x1002, 0x4E40); // TRAP #0
+        cpu.mmu.write_word(0x1004, 0x4AFC); // ILLEGAL
+        cpu.write_ssp(0x2000);
+        cpu.write_sr(0xA700);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x4000);
+        assert_eq!(cpu.read_sr(), 0x2700);
+        assert_eq!(cpu.mmu.read_word(0x1FFA), 0xA700);
+        assert_eq!(cpu.mmu.read_long(0x1FFC), 0x1002);
+
+        // Traced after the TRAP, with the TRAP handler stacked
+        cpu.write_ssp(0x2000);
+        cpu.write_sr(0xA700);
+        cpu.write_pc(0x1002);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x4000);
+        assert_eq!(cpu.read_ssp(), 0x1FF4);
+        assert_eq!(cpu.mmu.read_long(0x1FF6), 0x5000);
+
+        // An illegal instruction is not traced
+        cpu.write_ssp(0x2000);
+        cpu.write_sr(0xA700);
+        cpu.write_pc(0x1004);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x6000);
+        assert_eq!(cpu.read_ssp(), 0x1FFA);
+    }
+
+    /// RAM at 0-0xFFFF and a ROM at 0x10000, nothing else mapped
+    fn sparse_cpu(rom: Rom) -> Cpu {
+        let mut mmu = Mmu::empty();
+        mmu.map(0, 0x10000, Ram::new(0x10000));
+        mmu.map(0x10000, 0x100, rom);
+        let mut cpu = Cpu::with_mmu(mmu);
+        cpu.mmu.write_long(Vector::BusError as u32, 0x5000);
+        cpu.write_sr(0x2700);
+        cpu.write_ssp(0x2000);
+        cpu
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_long(0, 0x8000);
+        cpu.mmu.write_long(4, 0x1000);
+        cpu.write_sr(0x8004);
+        let start = cpu.cycles();
+        cpu.reset_exception().unwrap();
+        assert_eq!(cpu.cycles() - start, 40);
+        assert_eq!(cpu.read_sr(), 0x2700);
+        assert_eq!(cpu.read_ssp(), 0x8000);
+        assert_eq!(cpu.read_pc(), 0x1000);
+
+        // Odd PC
+        cpu.mmu.write_long(4, 0x1001);
+        let err = cpu.reset_exception().unwrap_err();
+        assert_eq!(err.reason, StopReason::DoubleFault);
+    }
+
+    #[test]
+    fn test_reset_overlay() {
+        let mut rom = vec![0; 0x100];
+        rom[..8].copy_from_slice(&[0, 0, 0x80, 0, 0, 0, 0x10, 0]);
+        let mut cpu = sparse_cpu(Rom::new(rom));
+        cpu.mmu.set_overlay(Some(0x10000));
+        cpu.reset_exception().unwrap();
+        assert_eq!(cpu.read_ssp(), 0x8000);
+        assert_eq!(cpu.read_pc(), 0x1000);
+        // RAM is visible at 0 once booted
+        assert_eq!(cpu.mmu.read_long(Vector::BusError as u32), 0x5000);
+    }
+
+    #[test]
+    fn test_bus_error() {
+        let mut cpu = sparse_cpu(Rom::new(vec![0; 0x100]));
+        cpu.mmu.write_word(0x1000, 0x3010); // MOVE.w (A0), D0
+        cpu.write_ar(0, 0x20_0000);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x5000);
+        assert_eq!(cpu.read_ssp(), 0x1FF2);
+        assert_eq!(cpu.mmu.read_word(0x1FF2), 0b1_0101);
+        assert_eq!(cpu.mmu.read_long(0x1FF4), 0x20_0000);
+        assert_eq!(cpu.mmu.read_word(0x1FF8), 0x3010);
+    }
+
+    #[test]
+    fn test_rom_writes() {
+        let mut cpu = sparse_cpu(Rom::new(vec![0x12; 0x100]));
+        cpu.mmu.write_word(0x1000, 0x3080); // MOVE.w D0, (A0)
+        cpu.write_ar(0, 0x10000);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x1002);
+        assert_eq!(cpu.mmu.read_word(0x10000), 0x1212);
+
+        let mut cpu = sparse_cpu(Rom::new(vec![0x12; 0x100]).fault_on_write(true));
+        cpu.mmu.write_word(0x1000, 0x3080); // MOVE.w D0, (A0)
+        cpu.write_ar(0, 0x10000);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x5000);
+        // Write cycle
+        assert_eq!(cpu.mmu.read_word(0x1FF2), 0b0_0101);
+        assert_eq!(cpu.mmu.read_word(0x10000), 0x1212);
+    }
+
+    #[test]
+    fn test_address_error_frame() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_long(Vector::AddressError as u32, 0x5000);
+        cpu.write_sr(0x2700);
+        cpu.write_ssp(0x2000);
+        cpu.write_pc(0x1004);
+        cpu.ir = 0x3010;
+        let fault = AccessFault {
+            address: 0x3001,
+            read: true,
+            exception: false,
+            function_code: FunctionCode::SupervisorData,
+        };
+        cpu.access_error(Vector::AddressError as u32, fault)
+            .unwrap();
+        assert_eq!(cpu.read_pc(), 0x5000);
+        assert_eq!(cpu.read_ssp(), 0x1FF2);
+        assert_eq!(cpu.mmu.read_word(0x1FF2), 0b1_0101);
+        assert_eq!(cpu.mmu.read_long(0x1FF4), 0x3001);
+        assert_eq!(cpu.mmu.read_word(0x1FF8), 0x3010);
+        assert_eq!(cpu.mmu.read_word(0x1FFA), 0x2700);
+        assert_eq!(cpu.mmu.read_long(0x1FFC), 0x1004);
+    }
+
+    #[test]
+    fn test_misaligned_access() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_long(Vector::AddressError as u32, 0x5000);
+        cpu.mmu.write_word(0x1000, 0x3010); // MOVE.w (A0), D0
+        cpu.write_sr(0x2700);
+        cpu.write_ssp(0x2000);
+        cpu.write_ar(0, 0x3001);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x5000);
+        assert_eq!(cpu.read_ssp(), 0x1FF2);
+        assert_eq!(cpu.mmu.read_word(0x1FF2), 0b1_0101);
+        assert_eq!(cpu.mmu.read_long(0x1FF4), 0x3001);
+        assert_eq!(cpu.mmu.read_word(0x1FF8), 0x3010);
+    }
+
+    #[test]
+    fn test_illegal_opcodes() {
+        let mut cpu = Cpu::default();
+        cpu.mmu
+            .write_long(Vector::IllegalInstruction as u32, 0x4000);
+        cpu.mmu.write_long(Vector::UnimplementedA as u32, 0x5000);
+        cpu.mmu.write_word(0x1000, 0x4E7A);
+        cpu.mmu.write_word(0x1002, 0xA000);
+        cpu.write_ssp(0x2000);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x4000);
+        assert_eq!(cpu.mmu.read_long(0x1FFC), 0x1000);
+        cpu.write_pc(0x1002);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x5000);
+        assert_eq!(cpu.mmu.read_long(0x1FF6), 0x1002);
+
+        cpu.stop_on_illegal = true;
+        cpu.write_pc(0x1000);
+        let err = cpu.step().unwrap_err();
+        assert_eq!(err.reason, StopReason::IllegalEncoding);
+        assert_eq!(err.pc, 0x1000);
+        assert_eq!(err.opcode, 0x4E7A);
+        assert_eq!(cpu.read_pc(), 0x1000);
+    }
+
+    #[test]
+    fn test_stop_reasons() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0xFFFF);
+        cpu.write_pc(0x1000);
+        let err = cpu.step().unwrap_err();
+        assert_eq!(err.reason, StopReason::Halted);
+        assert_eq!(cpu.read_pc(), 0x1000);
+
+        cpu.mmu.write_word(0x1000, 0x4E4F); // TRAP #15
+        cpu.write_dr_long(0, 11);
+        let err = cpu.run();
+        assert_eq!(err.reason, StopReason::Unimplemented);
+        assert_eq!(err.opcode, 0x4E4F);
+        assert_eq!(cpu.read_pc(), 0x1000);
+    }
+
+    #[test]
+    fn test_double_fault() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0x3010); // MOVE.w (A0), D0
+        cpu.write_ar(0, 0x3001);
+        cpu.write_ssp(0x2001);
+        cpu.write_pc(0x1000);
+        let err = cpu.step().unwrap_err();
+        assert_eq!(err.reason, StopReason::DoubleFault);
+        assert_eq!(err.pc, 0x1000);
+        assert_eq!(cpu.step().unwrap_err().reason, StopReason::DoubleFault);
+    }
+}
@@ -0,0 +1,196 @@
+use log::trace;
+
+use super::{cpu::Cpu, exception::CpuResult};
+use crate::Vector;
+
+/// Response of the interrupting device to the interrupt acknowledge cycle
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum InterruptAck {
+    /// Device asserted VPA, use the autovector for the interrupt level
+    #[default]
+    Autovector,
+    /// Device placed a vector number on the data bus
+    Vectored(u8),
+    /// No device responded and the acknowledge cycle ended in a bus error
+    Spurious,
+}
+
+impl Cpu {
+    /// Drive the IPL inputs to `level`, 0 meaning no interrupt requested.
+    /// The level stays asserted until changed, the device is expected to
+    /// lower it once serviced.
+    pub fn set_ipl(&mut self, level: u8, ack: InterruptAck) {
+        assert!(level < 8, "Interrupt level out of range");
+        // Level 7 is edge triggered and is taken once per transition to 7
+        if level == 7 && self.ipl != 7 {
+            self.nmi_pending = true;
+        }
+        self.ipl = level;
+        self.iack = ack;
+    }
+
+    pub fn read_ipl(&self) -> u8 {
+        self.ipl
+    }
+
+    /// Interrupt mask from bits 8-10 of SR
+    pub fn interrupt_mask(&self) -> u8 {
+        ((self.read_sr() >> 8) & 0b111) as u8
+    }
+
+    /// Whether the current IPL will be serviced before the next instruction
+    pub fn interrupt_pending(&self) -> bool {
+        match self.ipl {
+            0 => false,
+            7 => self.nmi_pending || self.interrupt_mask() < 7,
+            level => level > self.interrupt_mask(),
+        }
+    }
+
+    /// Interrupt exception processing for the current IPL. The mask is raised
+    /// to the level being serviced after the old SR is saved.
+    pub(super) fn interrupt(&mut self) -> CpuResult {
+        let level = self.ipl;
+        if level == 7 {
+            self.nmi_pending = false;
+        }
+        let vector = match self.iack {
+            InterruptAck::Autovector => Vector::Spurious as u32 + u32::from(level) * 4,
+            InterruptAck::Vectored(n) => u32::from(n) * 4,
+            InterruptAck::Spurious => Vector::Spurious as u32,
+        };
+        trace!("Interrupt level {level} through {vector:#X}");
+        self.idle(6);
+        self.iack_cycle(level, (vector / 4) as u8);
+        let sr = self.enter_supervisor();
+        self.write_sr((self.read_sr() & !0b0000_0111_0000_0000) | (u16::from(level) << 8));
+        self.push_frame(vector, self.read_pc(), sr)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::InterruptAck;
+    use crate::{vm::cpu::Cpu, AccessKind, StopReason, Transaction, Vector};
+
+    fn setup() -> Cpu {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_long(Vector::Level3 as u32, 0x3000);
+        cpu.mmu.write_long(Vector::Level7 as u32, 0x7000);
+        cpu.mmu.write_long(Vector::Spurious as u32, 0x6000);
+        cpu.mmu.write_long(0x40 * 4, 0x4000);
+        cpu.mmu.write_word(0x1000, 0x4E71); // NOP
+        cpu.write_sr(0x2200);
+        cpu.write_ssp(0x2000);
+        cpu.write_pc(0x1000);
+        cpu
+    }
+
+    #[test]
+    fn test_autovector() {
+        let mut cpu = setup();
+        cpu.set_ipl(3, InterruptAck::Autovector);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x3000);
+        assert_eq!(cpu.read_sr(), 0x2300);
+        assert_eq!(cpu.mmu.read_word(0x1FFA), 0x2200);
+        assert_eq!(cpu.mmu.read_long(0x1FFC), 0x1000);
+        // Masked while the level is being serviced
+        assert!(!cpu.interrupt_pending());
+    }
+
+    #[test]
+    fn test_acknowledge_cycle() {
+        let mut cpu = setup();
+        cpu.set_ipl(5, InterruptAck::Vectored(0x40));
+        cpu.record_transactions(true);
+        cpu.step().unwrap();
+        let ack = cpu.take_transactions().into_iter().find(|t| {
+            matches!(
+                t,
+                Transaction::Access {
+                    function_code: 7,
+                    ..
+                }
+            )
+        });
+        assert!(matches!(
+            ack,
+            Some(Transaction::Access {
+                kind: AccessKind::Read,
+                address: 0xFFFFFB,
+                value: 0x40,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_masked() {
+        let mut cpu = setup();
+        cpu.set_ipl(2, InterruptAck::Autovector);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x1002);
+    }
+
+    #[test]
+    fn test_vectored_and_spurious() {
+        let mut cpu = setup();
+        cpu.set_ipl(5, InterruptAck::Vectored(0x40));
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x4000);
+        assert_eq!(cpu.interrupt_mask(), 5);
+
+        let mut cpu = setup();
+        cpu.set_ipl(4, InterruptAck::Spurious);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x6000);
+        assert_eq!(cpu.interrupt_mask(), 4);
+    }
+
+    #[test]
+    fn test_nmi() {
+        let mut cpu = setup();
+        cpu.write_sr(0x2700);
+        cpu.mmu.write_word(0x7000, 0x4E71);
+        cpu.set_ipl(7, InterruptAck::Autovector);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x7000);
+        // Not retaken while the level stays at 7
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x7002);
+    }
+
+    #[test]
+    fn test_stop() {
+        let mut cpu = setup();
+        cpu.mmu.write_word(0x1000, 0x4E72); // STOP #0x2000
+        cpu.mmu.write_word(0x1002, 0x2000);
+        cpu.step().unwrap();
+        assert!(cpu.is_stopped());
+        assert_eq!(cpu.read_pc(), 0x1004);
+        assert_eq!(cpu.read_sr(), 0x2000);
+        let err = cpu.step().unwrap_err();
+        assert_eq!(err.reason, StopReason::Stopped);
+        assert_eq!(err.pc, 0x1000);
+        // Woken by an interrupt above the mask
+        cpu.set_ipl(3, InterruptAck::Autovector);
+        cpu.step().unwrap();
+        assert!(!cpu.is_stopped());
+        assert_eq!(cpu.read_pc(), 0x3000);
+        assert_eq!(cpu.mmu.read_long(0x1FFC), 0x1004);
+    }
+
+    #[test]
+    fn test_stop_traced() {
+        let mut cpu = setup();
+        cpu.mmu.write_long(Vector::Trace as u32, 0x5000);
+        cpu.mmu.write_word(0x1000, 0x4E72); // STOP #0x2700
+        cpu.mmu.write_word(0x1002, 0x2700);
+        cpu.write_sr(0xA000);
+        cpu.step().unwrap();
+        assert!(!cpu.is_stopped());
+        assert_eq!(cpu.read_pc(), 0x5000);
+        assert_eq!(cpu.mmu.read_word(0x1FFA), 0x2700);
+    }
+}
@@ -3,16 +3,20 @@ use log::trace;
 use crate::{
     types::{AddressingMode, Size, Value},
     util::{
-        get_bits, get_reg, get_size, is_bit_set, is_carry, is_negative, is_overflow,
-        sign_extend_16_to_32, sign_extend_8_to_32, SizeCoding,
+        add_carry_overflow, get_bits, get_reg, get_size, is_bit_set, is_carry, is_negative,
+        is_overflow, sign_extend_16_to_32, sub_carry_overflow, SizeCoding,
+    },
+    vm::{
+        cpu::Cpu,
+        ea::{address_step, Location},
+        exception::CpuResult,
     },
-    vm::cpu::Cpu,
     StatusRegister as SR,
 };
 
-impl<'a> Cpu<'a> {
-    pub fn add_family(&mut self, inst: u16) {
-        if get_bits(inst, 4, 2) == 0 && is_bit_set(inst, 8) {
+impl Cpu {
+    pub fn add_family(&mut self, inst: u16) -> CpuResult {
+        if get_bits(inst, 4, 2) == 0 && is_bit_set(inst, 8) && get_bits(inst, 6, 2) != 0b11 {
             return self.addx(inst);
         }
 
@@ -23,152 +27,165 @@ impl<'a> Cpu<'a> {
         self.add(inst)
     }
 
-    fn addx(&mut self, inst: u16) {
-        if is_bit_set(inst, 3) {
-            self.addx_addr(inst);
-        } else {
-            self.addx_data(inst);
-        }
+    fn addx(&mut self, inst: u16) -> CpuResult {
+        self.extend_op(inst, false)
     }
 
-    fn addx_data(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let reg1 = get_reg(inst, 9);
-        let reg2 = get_reg(inst, 0);
-        let val1 = self.read_dr(reg1);
-        let val2 = self.read_dr(reg2);
-
-        let res = match size {
-            Size::Byte => sign_extend_8_to_32(
-                (val1 as u8)
-                    .wrapping_add(val2 as u8)
-                    .wrapping_add(self.read_ccr(SR::X) as u8),
-            ),
-            Size::Word => sign_extend_16_to_32(
-                (val1 as u16)
-                    .wrapping_add(val2 as u16)
-                    .wrapping_add(self.read_ccr(SR::X) as u16),
-            ),
-            Size::Long => val1
-                .wrapping_add(val2)
-                .wrapping_add(self.read_ccr(SR::X) as u32),
+    /// ADDX and SUBX, either Dy,Dx or -(Ay),-(Ax) with the source read first.
+    /// Z is only ever cleared so that multi-precision results can be tested
+    /// as a whole.
+    pub(super) fn extend_op(&mut self, inst: u16, subtract: bool) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let rx = get_reg(inst, 9);
+        let ry = get_reg(inst, 0);
+        let name = if subtract { "SUBX" } else { "ADDX" };
+        let x = self.read_ccr(SR::X) as u32;
+        let (dst_loc, src, dst) = if is_bit_set(inst, 3) {
+            trace!("{name}.{size} -(A{ry}), -(A{rx})");
+            let src_addr = self.resolve_ea(
+                AddressingMode::AddressRegisterIndirectPreDecrement(ry),
+                size,
+            )?;
+            let src = self.read_location(Location::Memory(src_addr), size)?;
+            // Only one internal cycle for both decrements
+            self.decrement_ar(rx, address_step(rx, size));
+            let dst_loc = Location::Memory(self.read_ar(rx));
+            let dst = self.read_location(dst_loc, size)?;
+            (dst_loc, src, dst)
+        } else {
+            trace!("{name}.{size} D{ry}, D{rx}");
+            if size == Size::Long {
+                self.idle_after_prefetch(4)?;
+            }
+            let src = self.read_dr(ry) & size.mask();
+            let dst = self.read_dr(rx) & size.mask();
+            (Location::DataRegister(rx), src, dst)
         };
-
-        self.write_dr(reg1, size, res);
-
-        trace!("ADDX.{} D{}, D{}", size, reg1, reg2);
-        add_set_ccr(self, val1, val2, res, size);
-    }
-
-    fn addx_addr(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let reg1 = get_reg(inst, 9);
-        let reg2 = get_reg(inst, 0);
-        let ea1 = AddressingMode::AddressRegisterIndirectPreDecrement(reg1);
-        let ea2 = AddressingMode::AddressRegisterIndirectPreDecrement(reg2);
-        let val1: u32 = self.read_ea(ea1, size).into();
-        let val2: u32 = self.read_ea(ea2, size).into();
-
-        let res = val1
-            .wrapping_add(val2)
-            .wrapping_add(self.read_ccr(SR::X) as u32);
-
-        trace!("ADDX.{} -(A{}), -(A{})", size, reg1, reg2);
-        add_set_ccr(self, val1, val2, res, size); // TODO - flags not right
+        let (res, (carry, overflow)) = if subtract {
+            let res = dst.wrapping_sub(src).wrapping_sub(x);
+            (res, sub_carry_overflow(dst, src, res, size))
+        } else {
+            let res = dst.wrapping_add(src).wrapping_add(x);
+            (res, add_carry_overflow(dst, src, res, size))
+        };
+        let res = res & size.mask();
+        self.write_location(dst_loc, size, res)?;
+        self.write_ccr(SR::X, carry);
+        self.write_ccr(SR::C, carry);
+        self.write_ccr(SR::V, overflow);
+        self.write_ccr(SR::N, is_negative(res, size));
+        if res != 0 {
+            self.write_ccr(SR::Z, false);
+        }
+        Ok(())
     }
 
-    fn adda(&mut self, inst: u16) {
+    fn adda(&mut self, inst: u16) -> CpuResult {
         let size = if is_bit_set(inst, 8) {
             Size::Long
         } else {
             Size::Word
         };
-        let ea = AddressingMode::from(inst);
+        let ea = AddressingMode::try_from(inst)?;
         let val = match size {
             Size::Word => {
-                let v = self.read_ea_word(ea);
+                let v = self.read_ea_word(ea)?;
                 sign_extend_16_to_32(v)
             }
-            Size::Long => self.read_ea_long(ea),
+            Size::Long => self.read_ea_long(ea)?,
             Size::Byte => unreachable!(),
         };
         let reg = get_reg(inst, 9);
         let addr = self.read_ar(reg);
         let res = addr.wrapping_add(val);
+        self.idle_after_prefetch(if size == Size::Word || ea.is_register_or_immediate() {
+            4
+        } else {
+            2
+        })?;
         trace!("ADDA.{size} {ea}, A{reg}");
         self.write_ar(reg, res);
+        Ok(())
     }
 
-    fn add(&mut self, inst: u16) {
+    fn add(&mut self, inst: u16) -> CpuResult {
         if is_bit_set(inst, 8) {
-            self.add_addr(inst);
+            self.add_addr(inst)
         } else {
-            self.add_data(inst);
+            self.add_data(inst)
         }
     }
 
-    fn add_addr(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
+    fn add_addr(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
         let dreg = get_reg(inst, 9);
         let val1 = self.read_dr(dreg);
-        let ea = AddressingMode::from(inst);
-        let val2 = self.read_ea(ea, size).into();
+        let ea = AddressingMode::try_from(inst)?;
+        let val2 = self.read_ea(ea, size)?.into();
         let res = match size {
             Size::Byte => Value::Byte((val1 as u8).wrapping_add(val2 as u8)),
             Size::Word => Value::Word((val1 as u16).wrapping_add(val2 as u16)),
             Size::Long => Value::Long((val1).wrapping_add(val2)),
         };
-        self.write_ea(ea, size, res);
+        self.write_ea(ea, size, res)?;
         trace!("ADD D{} {}", dreg, ea);
         add_set_ccr(self, val1, val2, res.into(), size);
+        Ok(())
     }
 
-    fn add_data(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
+    fn add_data(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
         let dreg = get_reg(inst, 9);
         let val1 = self.read_dr(dreg);
-        let ea = AddressingMode::from(inst);
-        let val2 = self.read_ea(ea, size).into();
+        let ea = AddressingMode::try_from(inst)?;
+        let val2 = self.read_ea(ea, size)?.into();
         let res = match size {
             Size::Byte => Value::Byte((val1 as u8).wrapping_add(val2 as u8)),
             Size::Word => Value::Word((val1 as u16).wrapping_add(val2 as u16)),
             Size::Long => Value::Long((val1).wrapping_add(val2)),
         };
+        if size == Size::Long {
+            self.idle_after_prefetch(if ea.is_register_or_immediate() { 4 } else { 2 })?;
+        }
         self.write_dr(dreg, size, res.into());
         trace!("ADD {} D{}", ea, dreg);
         add_set_ccr(self, val1, val2, res.into(), size);
+        Ok(())
     }
 
-    pub(crate) fn addi(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let ea = AddressingMode::from(inst);
-        let val: u32 = self.read_ea(ea, size).into();
-        let imm = match size {
-            Size::Byte => (self.fetch_word() as u8) as u32,
-            Size::Word => self.fetch_word() as u32,
-            Size::Long => self.fetch_long(),
-        };
-        let res = match size {
-            Size::Byte => Value::Byte((imm as u8).wrapping_add(val as u8)),
-            Size::Word => Value::Word((imm as u16).wrapping_add(val as u16)),
-            Size::Long => Value::Long(imm.wrapping_add(val)),
-        };
-        trace!("ADDI.{size} {imm:#X} {ea}");
-        self.write_ea(ea, size, res);
-        add_set_ccr(self, val, imm, res.into(), size);
+    pub(crate) fn addi(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let imm = self.fetch_immediate(size)?;
+        let ea = AddressingMode::try_from(inst)?;
+        trace!("ADDI.{size} #{imm:#X}, {ea}");
+        let loc = self.locate(ea, size)?;
+        let dst = self.read_location(loc, size)?;
+        if size == Size::Long && matches!(loc, Location::DataRegister(_)) {
+            self.idle_after_prefetch(4)?;
+        }
+        let res = dst.wrapping_add(imm) & size.mask();
+        self.write_location(loc, size, res)?;
+        add_set_ccr(self, dst, imm, res, size);
+        Ok(())
     }
 
-    pub(crate) fn addq(&mut self, inst: u16) {
+    pub(crate) fn addq(&mut self, inst: u16) -> CpuResult {
         let data = get_bits(inst, 9, 3);
         let imm = if data == 0 { 8 } else { data as u8 };
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let ea = AddressingMode::from(inst);
-        let val = self.read_ea(ea, size);
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let ea = AddressingMode::try_from(inst)?;
+        let val = self.read_ea(ea, size)?;
         let res = val + imm;
+        if matches!(ea, AddressingMode::AddressRegisterDirect(_))
+            || (size == Size::Long && ea.is_register_or_immediate())
+        {
+            self.idle_after_prefetch(4)?;
+        }
         trace!("ADDQ.{size} {imm}, {ea} ({val:X})");
-        self.write_ea(ea, size, res);
+        self.write_ea(ea, size, res)?;
 
         add_set_ccr(self, val.into(), imm.into(), res.into(), size);
+        Ok(())
     }
 }
 
@@ -179,3 +196,31 @@ fn add_set_ccr(cpu: &mut Cpu, val1: u32, val2: u32, res: u32, size: Size) {
     cpu.write_ccr(SR::Z, res == 0);
     cpu.write_ccr(SR::V, is_overflow(val1, val2, res, size));
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{vm::cpu::Cpu, StatusRegister as SR};
+
+    #[test]
+    fn test_addi_memory() {
+        let mut cpu = Cpu::default();
+        // ADDI.W #0x8001, 4(A0) then ADDI.B #1, -(A1)
+        for (i, word) in [0x0668, 0x8001, 0x0004, 0x0621, 0x0001].iter().enumerate() {
+            cpu.mmu.write_word(0x1000 + 2 * i as u32, *word);
+        }
+        cpu.mmu.write_word(0x2004, 0x8000);
+        cpu.mmu.write_byte(0x3000, 0xFF);
+        cpu.write_ar(0, 0x2000);
+        cpu.write_ar(1, 0x3001);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.mmu.read_word(0x2004), 0x0001);
+        assert!(cpu.read_ccr(SR::C));
+        assert!(cpu.read_ccr(SR::V));
+        cpu.step().unwrap();
+        assert_eq!(cpu.mmu.read_byte(0x3000), 0);
+        assert_eq!(cpu.read_ar(1), 0x3000);
+        assert!(cpu.read_ccr(SR::Z));
+        assert_eq!(cpu.read_pc(), 0x100A);
+    }
+}
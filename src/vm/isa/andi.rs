@@ -1,9 +1,14 @@
 use log::{error, trace};
 
-use crate::{types::AddressingMode, util::get_size, vm::cpu::Cpu, Vector};
+use crate::{
+    types::{AddressingMode, Size},
+    util::{get_size, is_negative},
+    vm::{cpu::Cpu, ea::Location, exception::CpuResult},
+    StatusRegister as SR,
+};
 
-impl<'a> Cpu<'a> {
-    pub(super) fn andi_family(&mut self, inst: u16) {
+impl Cpu {
+    pub(super) fn andi_family(&mut self, inst: u16) -> CpuResult {
         if inst == 0b0000_0010_0011_1100 {
             return self.andi_to_ccr();
         }
@@ -11,32 +16,46 @@ impl<'a> Cpu<'a> {
             return self.andi_to_sr();
         }
 
-        self.andi(inst);
+        self.andi(inst)
     }
 
-    fn andi_to_ccr(&mut self) {
-        let val = self.fetch_word() & 0xFF;
+    fn andi_to_ccr(&mut self) -> CpuResult {
+        let val = self.fetch_word()? & 0xFF;
         let old = self.read_sr();
+        self.idle(12);
         trace!("ANDI to CCR {val:#010b}");
         self.write_sr((old & 0xFF00) + ((old & 0xFF) & val));
+        Ok(())
     }
 
-    fn andi_to_sr(&mut self) {
+    fn andi_to_sr(&mut self) -> CpuResult {
         if !self.is_supervisor_mode() {
             error!("Not supervisor");
-            self.trap_vec(Vector::PrivilegeViolation as u32);
+            return self.privilege_violation();
         }
-        let val = self.fetch_word();
+        let val = self.fetch_word()?;
         let old = self.read_sr();
+        self.idle(12);
         trace!("ANDI to SR {val:#018b}");
         self.write_sr(old & (val & 0b1010_0111_1111_1111));
+        Ok(())
     }
 
-    fn andi(&mut self, inst: u16) {
-        let size = get_size(inst, 6, crate::util::SizeCoding::Pink);
-        let ea = AddressingMode::from(inst);
-        let val = self.read_ea(ea, size);
-        trace!("ANDI.{size} {ea} ({val})");
-        todo!()
+    fn andi(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, crate::util::SizeCoding::Pink)?;
+        let imm = self.fetch_immediate(size)?;
+        let ea = AddressingMode::try_from(inst)?;
+        trace!("ANDI.{size} #{imm:#X}, {ea}");
+        let loc = self.locate(ea, size)?;
+        let res = self.read_location(loc, size)? & imm;
+        if size == Size::Long && matches!(loc, Location::DataRegister(_)) {
+            self.idle_after_prefetch(2)?;
+        }
+        self.write_location(loc, size, res)?;
+        self.write_ccr(SR::N, is_negative(res, size));
+        self.write_ccr(SR::Z, res == 0);
+        self.write_ccr(SR::V, false);
+        self.write_ccr(SR::C, false);
+        Ok(())
     }
 }
@@ -1,61 +1,121 @@
-use std::u8;
-
 use log::trace;
 
-use crate::types::AddressingMode;
+use std::fmt::Display;
+
+use crate::types::{AddressingMode, ExtensionMode, Size};
 use crate::util::{get_bits, get_reg, is_bit_set};
-use crate::vm::Cpu;
+use crate::vm::{
+    exception::{CpuResult, Exception},
+    Cpu,
+};
 use crate::StatusRegister as SR;
 
-impl<'a> Cpu<'a> {
-    pub(crate) fn bit_family(&mut self, inst: u16) {
-        if get_bits(inst, 3, 3) == 0b001 {
-            return self.movep(inst);
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum BitOp {
+    Test,
+    Change,
+    Clear,
+    Set,
+}
+
+impl BitOp {
+    fn apply(self, val: u32, bit: u8) -> u32 {
+        match self {
+            BitOp::Test => val,
+            BitOp::Change => val ^ (1 << bit),
+            BitOp::Clear => val & !(1 << bit),
+            BitOp::Set => val | (1 << bit),
         }
-        match get_bits(inst, 6, 2) {
-            0b00 => self.btst(inst),
-            0b01 => self.bchg(inst),
-            0b10 => self.bclr(inst),
-            0b11 => self.bset(inst),
-            _ => unreachable!(),
+    }
+}
+
+impl Display for BitOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitOp::Test => write!(f, "BTST"),
+            BitOp::Change => write!(f, "BCHG"),
+            BitOp::Clear => write!(f, "BCLR"),
+            BitOp::Set => write!(f, "BSET"),
         }
     }
+}
 
-    fn btst(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        let modulo: u8 = if let AddressingMode::DataRegisterDirect(_) = ea {
-            32
-        } else {
-            8
+impl Cpu {
+    pub(crate) fn bit_family(&mut self, inst: u16) -> CpuResult {
+        if get_bits(inst, 3, 3) == 0b001 {
+            if !is_bit_set(inst, 8) {
+                return Err(Exception::Illegal);
+            }
+            return self.movep(inst);
+        }
+        let op = match get_bits(inst, 6, 2) {
+            0b00 => BitOp::Test,
+            0b01 => BitOp::Change,
+            0b10 => BitOp::Clear,
+            0b11 => BitOp::Set,
+            _ => unreachable!(),
         };
-        let val = self.read_ea_byte(ea);
+        self.bit_op(inst, op)
+    }
 
-        let res = if is_bit_set(inst, 8) {
-            // Reg
-            let reg = get_reg(inst, 9);
-            let bit = self.read_dr(reg) as u8 % modulo;
-            is_bit_set(val, bit)
+    /// BTST, BCHG, BCLR and BSET. The bit number comes from Dn or an
+    /// immediate word and is taken modulo 32 for data registers and modulo 8
+    /// for memory bytes. Z is set when the tested bit was clear.
+    fn bit_op(&mut self, inst: u16, op: BitOp) -> CpuResult {
+        let dynamic = is_bit_set(inst, 8);
+        let bit = if dynamic {
+            self.read_dr(get_reg(inst, 9)) as u8
         } else {
-            // Imm
-            let bit = ((self.fetch_word() & 0xFF) as u8) % modulo;
-            is_bit_set(val, bit)
+            // Bits 8-15 of the immediate are ignored
+            self.fetch_word()? as u8
         };
-        self.write_ccr(SR::Z, res);
-    }
-
-    fn bchg(&mut self, _inst: u16) {
-        todo!()
-    }
+        let ea = AddressingMode::try_from(inst)?;
+        match ea {
+            AddressingMode::AddressRegisterDirect(_) => return Err(Exception::Illegal),
+            AddressingMode::Extension(ExtensionMode::Immediate)
+                if !dynamic || op != BitOp::Test =>
+            {
+                return Err(Exception::Illegal)
+            }
+            AddressingMode::Extension(
+                ExtensionMode::PcRelativeDisplacement | ExtensionMode::PcRelativeIndex,
+            ) if op != BitOp::Test => return Err(Exception::Illegal),
+            _ => {}
+        }
 
-    fn bclr(&mut self, _inst: u16) {
-        todo!()
-    }
+        if let AddressingMode::DataRegisterDirect(reg) = ea {
+            let bit = bit % 32;
+            trace!("{op} #{bit}, D{reg}");
+            let val = self.read_dr(reg);
+            self.write_ccr(SR::Z, !is_bit_set(val, bit));
+            self.write_dr(reg, Size::Long, op.apply(val, bit));
+            // The ALU takes longer for bits in the high word
+            let high = if bit >= 16 { 2 } else { 0 };
+            self.idle_after_prefetch(match op {
+                BitOp::Test => 2,
+                BitOp::Change | BitOp::Set => 2 + high,
+                BitOp::Clear => 4 + high,
+            })?;
+            return Ok(());
+        }
 
-    fn bset(&mut self, _inst: u16) {
-        todo!()
+        let bit = bit % 8;
+        trace!("{op} #{bit}, {ea}");
+        if let AddressingMode::Extension(ExtensionMode::Immediate) = ea {
+            let val = self.fetch_word()? as u8;
+            self.write_ccr(SR::Z, !is_bit_set(val, bit));
+            return Ok(());
+        }
+        let addr = self.resolve_ea(ea, Size::Byte)?;
+        let val = self.read_byte_in(addr, ea.is_pc_relative())?;
+        self.write_ccr(SR::Z, !is_bit_set(val, bit));
+        if op != BitOp::Test {
+            self.write_byte(addr, op.apply(val.into(), bit) as u8)?;
+        }
+        Ok(())
     }
 
-    fn movep(&mut self, inst: u16) {
+    fn movep(&mut self, inst: u16) -> CpuResult {
         // movep.bin - D2: 00001234, D3: B00B9876, D4: 1234FFFF
         match get_bits(inst, 6, 2) {
             0b00 => self.movep_word_mem_to_reg(inst),
@@ -66,46 +126,48 @@ impl<'a> Cpu<'a> {
         }
     }
 
-    fn movep_word_mem_to_reg(&mut self, inst: u16) {
+    fn movep_word_mem_to_reg(&mut self, inst: u16) -> CpuResult {
         let addr = get_reg(inst, 0);
         let data = get_reg(inst, 9);
-        let displacement = self.fetch_signed_word();
+        let displacement = self.fetch_signed_word()?;
         let target = (self.read_ar(addr) as i64 + displacement as i64) as u32;
         if displacement == 0 {
             trace!("MOVEP.w (A{addr}), D{data}");
         } else {
             trace!("MOVEP.w {displacement}(A{addr}), D{data}");
         }
-        let high = self.mmu.read_byte(target);
-        let low = self.mmu.read_byte(target + 2);
+        let high = self.read_byte(target)?;
+        let low = self.read_byte(target + 2)?;
         self.write_dr_word(data, ((high as u16) << 8) + low as u16);
+        Ok(())
     }
 
-    fn movep_long_mem_to_reg(&mut self, inst: u16) {
+    fn movep_long_mem_to_reg(&mut self, inst: u16) -> CpuResult {
         let addr = get_reg(inst, 0);
         let data = get_reg(inst, 9);
-        let displacement = self.fetch_signed_word();
+        let displacement = self.fetch_signed_word()?;
         let target = (self.read_ar(addr) as i64 + displacement as i64) as u32;
         if displacement == 0 {
             trace!("MOVEP.l (A{addr}), D{data}");
         } else {
             trace!("MOVEP.l {displacement}(A{addr}), D{data}");
         }
-        let high = self.mmu.read_byte(target);
-        let mid_high = self.mmu.read_byte(target + 2);
-        let mid_low = self.mmu.read_byte(target + 4);
-        let low = self.mmu.read_byte(target + 6);
+        let high = self.read_byte(target)?;
+        let mid_high = self.read_byte(target + 2)?;
+        let mid_low = self.read_byte(target + 4)?;
+        let low = self.read_byte(target + 6)?;
         let val = ((high as u32) << 24)
             + ((mid_high as u32) << 16)
             + ((mid_low as u32) << 8)
             + low as u32;
         self.write_dr_long(data, val);
+        Ok(())
     }
 
-    fn movep_word_reg_to_mem(&mut self, inst: u16) {
+    fn movep_word_reg_to_mem(&mut self, inst: u16) -> CpuResult {
         let addr = get_reg(inst, 0);
         let data = get_reg(inst, 9);
-        let displacement = self.fetch_signed_word();
+        let displacement = self.fetch_signed_word()?;
         let target = (self.read_ar(addr) as i64 + displacement as i64) as u32;
         if displacement == 0 {
             trace!("MOVEP.w D{data}, (A{addr})");
@@ -115,14 +177,14 @@ impl<'a> Cpu<'a> {
         let val = self.read_dr(data);
         let high = (0xFF00 & val) >> 8;
         let low = 0xFF & val;
-        self.mmu.write_byte(target, high as u8);
-        self.mmu.write_byte(target + 2, low as u8);
+        self.write_byte(target, high as u8)?;
+        self.write_byte(target + 2, low as u8)
     }
 
-    fn movep_long_reg_to_mem(&mut self, inst: u16) {
+    fn movep_long_reg_to_mem(&mut self, inst: u16) -> CpuResult {
         let addr = get_reg(inst, 0);
         let data = get_reg(inst, 9);
-        let displacement = self.fetch_signed_word();
+        let displacement = self.fetch_signed_word()?;
         let target = (self.read_ar(addr) as i64 + displacement as i64) as u32;
         if displacement == 0 {
             trace!("MOVEP.l D{data}, (A{addr})");
@@ -134,9 +196,59 @@ impl<'a> Cpu<'a> {
         let mid_high = (0xFF0000 & val) >> 16;
         let mid_low = (0xFF00 & val) >> 8;
         let low = 0xFF & val;
-        self.mmu.write_byte(target, high as u8);
-        self.mmu.write_byte(target + 2, mid_high as u8);
-        self.mmu.write_byte(target + 4, mid_low as u8);
-        self.mmu.write_byte(target + 6, low as u8);
+        self.write_byte(target, high as u8)?;
+        self.write_byte(target + 2, mid_high as u8)?;
+        self.write_byte(target + 4, mid_low as u8)?;
+        self.write_byte(target + 6, low as u8)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{vm::cpu::Cpu, StatusRegister as SR};
+
+    fn run(words: &[u16]) -> Cpu {
+        let mut cpu = Cpu::default();
+        for (i, w) in words.iter().enumerate() {
+            cpu.mmu.write_word(0x1000 + 2 * i as u32, *w);
+        }
+        cpu.mmu.write_byte(0x2000, 0b0000_0100);
+        cpu.write_ar(0, 0x2000);
+        cpu.write_dr_long(0, 0x8000_0001);
+        cpu.write_dr_long(1, 34);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        cpu
+    }
+
+    #[test]
+    fn test_register() {
+        let cpu = run(&[0x0340]); // BCHG D1, D0
+        assert_eq!(cpu.read_dr(0), 0x8000_0005);
+        assert!(cpu.read_ccr(SR::Z));
+        let cpu = run(&[0x0880, 31]); // BCLR #31, D0
+        assert_eq!(cpu.read_dr(0), 0x0000_0001);
+        assert!(!cpu.read_ccr(SR::Z));
+        let cpu = run(&[0x08C0, 0xFF04]); // BSET #4, D0
+        assert_eq!(cpu.read_dr(0), 0x8000_0011);
+        let cpu = run(&[0x0300]); // BTST D1, D0
+        assert_eq!(cpu.read_dr(0), 0x8000_0001);
+        assert!(cpu.read_ccr(SR::Z));
+    }
+
+    #[test]
+    fn test_memory() {
+        // Bit 34 is bit 2 in memory
+        let cpu = run(&[0x0390]); // BCLR D1, (A0)
+        assert_eq!(cpu.mmu.read_byte(0x2000), 0);
+        assert!(!cpu.read_ccr(SR::Z));
+        let cpu = run(&[0x08D0, 9]); // BSET #9, (A0)
+        assert_eq!(cpu.mmu.read_byte(0x2000), 0b0000_0110);
+        assert!(cpu.read_ccr(SR::Z));
+        let cpu = run(&[0x0850, 2]); // BCHG #2, (A0)
+        assert_eq!(cpu.mmu.read_byte(0x2000), 0);
+        let cpu = run(&[0x0810, 2]); // BTST #2, (A0)
+        assert_eq!(cpu.mmu.read_byte(0x2000), 0b0000_0100);
+        assert!(!cpu.read_ccr(SR::Z));
     }
 }
@@ -3,23 +3,23 @@ use log::trace;
 use crate::{
     types::{AddressingMode, Size},
     util::{
-        get_reg, get_size, is_bit_set, is_carry, is_negative, is_overflow, sign_extend_16_to_32,
+        get_reg, get_size, is_bit_set, is_negative, sign_extend_16_to_32, sub_carry_overflow,
         SizeCoding,
     },
-    vm::cpu::Cpu,
+    vm::{cpu::Cpu, ea::Location, exception::CpuResult},
     StatusRegister as SR,
 };
 
-impl<'a> Cpu<'a> {
-    pub(super) fn cmp_family(&mut self, inst: u16) {
-        if (inst >> 3) & 0b111 == 0b001 {
-            return self.cmpm(inst);
-        }
-
+impl Cpu {
+    pub(super) fn cmp_family(&mut self, inst: u16) -> CpuResult {
         if (inst >> 6) & 0b11 == 0b11 {
             return self.cmpa(inst);
         }
 
+        if is_bit_set(inst, 8) && (inst >> 3) & 0b111 == 0b001 {
+            return self.cmpm(inst);
+        }
+
         if is_bit_set(inst, 8) {
             self.eor(inst)
         } else {
@@ -27,45 +27,180 @@ impl<'a> Cpu<'a> {
         }
     }
 
-    fn cmpm(&mut self, _inst: u16) {
-        todo!()
+    fn cmpm(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let rx = get_reg(inst, 9);
+        let ry = get_reg(inst, 0);
+        trace!("CMPM.{size} (A{ry})+, (A{rx})+");
+        let src_addr = self.resolve_ea(
+            AddressingMode::AddressRegisterIndirectPostIncrement(ry),
+            size,
+        )?;
+        let src = self.read_location(Location::Memory(src_addr), size)?;
+        let dst_addr = self.resolve_ea(
+            AddressingMode::AddressRegisterIndirectPostIncrement(rx),
+            size,
+        )?;
+        let dst = self.read_location(Location::Memory(dst_addr), size)?;
+        self.set_cmp_ccr(dst, src, size);
+        Ok(())
+    }
+
+    pub(crate) fn cmpi(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let imm = self.fetch_immediate(size)?;
+        let ea = AddressingMode::try_from(inst)?;
+        trace!("CMPI.{size} #{imm:#X}, {ea}");
+        let loc = self.locate(ea, size)?;
+        let dst = self.read_location(loc, size)?;
+        if size == Size::Long && matches!(loc, Location::DataRegister(_)) {
+            self.idle_after_prefetch(2)?;
+        }
+        self.set_cmp_ccr(dst, imm, size);
+        Ok(())
+    }
+
+    /// Flags of `dst - src`, X is not affected
+    fn set_cmp_ccr(&mut self, dst: u32, src: u32, size: Size) {
+        let res = dst.wrapping_sub(src) & size.mask();
+        let (carry, overflow) = sub_carry_overflow(dst, src, res, size);
+        self.write_ccr(SR::N, is_negative(res, size));
+        self.write_ccr(SR::Z, res == 0);
+        self.write_ccr(SR::V, overflow);
+        self.write_ccr(SR::C, carry);
     }
 
-    fn cmpa(&mut self, inst: u16) {
+    fn cmpa(&mut self, inst: u16) -> CpuResult {
         let reg = get_reg(inst, 9);
         let dest = self.read_ar(reg);
-        let ea = AddressingMode::from(inst);
+        let ea = AddressingMode::try_from(inst)?;
 
         let (size, src) = if is_bit_set(inst, 8) {
-            (Size::Long, self.read_ea_long(ea))
+            (Size::Long, self.read_ea_long(ea)?)
         } else {
-            (Size::Word, sign_extend_16_to_32(self.read_ea_word(ea)))
+            (Size::Word, sign_extend_16_to_32(self.read_ea_word(ea)?))
         };
-        let result = dest.wrapping_sub(src);
-
+        self.idle_after_prefetch(2)?;
         trace!("CMPA.{size} {ea} ({src:#X}) A{reg}");
-        self.write_ccr(SR::N, is_negative(result, size));
-        self.write_ccr(SR::Z, result == 0);
-        self.write_ccr(SR::V, is_overflow(dest, src, result, size));
-        self.write_ccr(SR::C, is_carry(dest, src, result, size));
+        // The source is sign extended and compared with the whole register
+        self.set_cmp_ccr(dest, src, Size::Long);
+        Ok(())
     }
 
-    fn cmp(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
+    fn cmp(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
         let reg = get_reg(inst, 9);
         let dest = self.read_dr(reg);
-        let ea = AddressingMode::from(inst);
-        let src: u32 = self.read_ea(ea, size).into();
-        let result = dest.wrapping_sub(src);
-
+        let ea = AddressingMode::try_from(inst)?;
+        let src: u32 = self.read_ea(ea, size)?.into();
+        if size == Size::Long {
+            self.idle_after_prefetch(2)?;
+        }
         trace!("CMP.{size} {ea} ({src:#X}) D{reg}");
-        self.write_ccr(SR::N, is_negative(result, size));
-        self.write_ccr(SR::Z, result == 0);
-        self.write_ccr(SR::V, is_overflow(dest, src, result, size));
-        self.write_ccr(SR::C, is_carry(dest, src, result, size));
+        self.set_cmp_ccr(dest, src, size);
+        Ok(())
     }
 
-    fn eor(&mut self, _inst: u16) {
-        todo!()
+    fn eor(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let reg = get_reg(inst, 9);
+        let ea = AddressingMode::try_from(inst)?;
+        trace!("EOR.{size} D{reg}, {ea}");
+        let loc = self.locate(ea, size)?;
+        let res = self.read_location(loc, size)? ^ (self.read_dr(reg) & size.mask());
+        if size == Size::Long && matches!(loc, Location::DataRegister(_)) {
+            self.idle_after_prefetch(4)?;
+        }
+        self.write_location(loc, size, res)?;
+        self.write_ccr(SR::N, is_negative(res, size));
+        self.write_ccr(SR::Z, res == 0);
+        self.write_ccr(SR::V, false);
+        self.write_ccr(SR::C, false);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{vm::cpu::Cpu, StatusRegister as SR};
+
+    #[test]
+    fn test_cmpm() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0xB348); // CMPM.w (A0)+, (A1)+
+        cpu.mmu.write_word(0x2000, 0x0002);
+        cpu.mmu.write_word(0x3000, 0x0001);
+        cpu.write_ar(0, 0x2000);
+        cpu.write_ar(1, 0x3000);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_ar(0), 0x2002);
+        assert_eq!(cpu.read_ar(1), 0x3002);
+        assert!(cpu.read_ccr(SR::C));
+        assert!(cpu.read_ccr(SR::N));
+        assert!(!cpu.read_ccr(SR::Z));
+    }
+
+    #[test]
+    fn test_eor() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0xB150); // EOR.w D0, (A0)
+        cpu.mmu.write_word(0x2000, 0x00FF);
+        cpu.write_dr_long(0, 0x80FF);
+        cpu.write_ar(0, 0x2000);
+        cpu.write_sr(0x2003);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.mmu.read_word(0x2000), 0x8000);
+        assert!(cpu.read_ccr(SR::N));
+        assert!(!cpu.read_ccr(SR::V));
+        assert!(!cpu.read_ccr(SR::C));
+    }
+
+    #[test]
+    fn test_cmp_borrow() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0xB200); // CMP.b D0, D1
+        cpu.mmu.write_word(0x1002, 0xB200); // CMP.b D0, D1
+        cpu.write_dr_long(0, 0x02);
+        cpu.write_dr_long(1, 0x01);
+        cpu.write_sr(0x2010);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert!(cpu.read_ccr(SR::C));
+        assert!(cpu.read_ccr(SR::N));
+        assert!(!cpu.read_ccr(SR::V));
+        assert!(!cpu.read_ccr(SR::Z));
+        // X is left alone
+        assert!(cpu.read_ccr(SR::X));
+        // 0x80 - 0x01 overflows into a positive result
+        cpu.write_dr_long(0, 0x01);
+        cpu.write_dr_long(1, 0x80);
+        cpu.step().unwrap();
+        assert!(cpu.read_ccr(SR::V));
+        assert!(!cpu.read_ccr(SR::C));
+        assert!(!cpu.read_ccr(SR::N));
+    }
+
+    #[test]
+    fn test_cmpa() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0xB2C0); // CMPA.w D0, A1
+        cpu.mmu.write_word(0x1002, 0xB3C0); // CMPA.l D0, A1
+
+        // 0xFFFF is sign extended to -1, which A1 holds
+        cpu.write_dr_long(0, 0xFFFF);
+        cpu.write_ar(1, 0xFFFF_FFFF);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert!(cpu.read_ccr(SR::Z));
+        assert!(!cpu.read_ccr(SR::C));
+        // 0x8000_0000 - 1 overflows without borrowing
+        cpu.write_dr_long(0, 1);
+        cpu.write_ar(1, 0x8000_0000);
+        cpu.step().unwrap();
+        assert!(cpu.read_ccr(SR::V));
+        assert!(!cpu.read_ccr(SR::C));
+        assert!(!cpu.read_ccr(SR::N));
     }
 }
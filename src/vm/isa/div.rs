@@ -1,14 +1,69 @@
 use log::trace;
 
 use crate::{
-    types::{AddressingMode, Value},
-    util::{get_bits, get_reg, get_size, is_bit_set, is_negative, SizeCoding},
-    vm::cpu::Cpu,
-    StatusRegister as SR,
+    types::{AddressingMode, Size, Value},
+    util::{bcd_sub, get_bits, get_reg, get_size, is_bit_set, is_negative, SizeCoding},
+    vm::{
+        cpu::Cpu,
+        exception::{CpuResult, Exception},
+    },
+    StatusRegister as SR, Vector,
 };
 
-impl<'a> Cpu<'a> {
-    pub(super) fn div_family(&mut self, inst: u16) {
+/// Clock cycles DIVU spends after reading its operand, following the
+/// shift and subtract steps of the microcode
+fn divu_cycles(dividend: u32, divisor: u16) -> u64 {
+    if dividend >> 16 >= divisor as u32 {
+        return 10;
+    }
+    let divisor = (divisor as u32) << 16;
+    let mut dividend = dividend;
+    let mut cycles = 76;
+    for _ in 0..15 {
+        let carry = dividend & 0x8000_0000 != 0;
+        dividend <<= 1;
+        if carry {
+            dividend = dividend.wrapping_sub(divisor);
+        } else {
+            cycles += 4;
+            if dividend >= divisor {
+                dividend -= divisor;
+                cycles -= 2;
+            }
+        }
+    }
+    cycles
+}
+
+/// Clock cycles DIVS spends after reading its operand, which depend on the
+/// signs of the operands and the bits of the absolute quotient
+fn divs_cycles(dividend: i32, divisor: i16) -> u64 {
+    let mut cycles = if dividend < 0 { 14 } else { 12 };
+    let abs_dividend = dividend.unsigned_abs();
+    let abs_divisor = divisor.unsigned_abs() as u32;
+    if abs_dividend >> 16 >= abs_divisor {
+        return cycles + 4;
+    }
+    cycles += 110;
+    if divisor >= 0 {
+        if dividend >= 0 {
+            cycles -= 2;
+        } else {
+            cycles += 2;
+        }
+    }
+    let quotient = abs_dividend / abs_divisor;
+    // 2 clock cycles for each clear bit in the top 15 bits of the quotient
+    for bit in 1..16 {
+        if quotient & (1 << bit) == 0 {
+            cycles += 2;
+        }
+    }
+    cycles
+}
+
+impl Cpu {
+    pub(super) fn div_family(&mut self, inst: u16) -> CpuResult {
         if inst >> 4 & 0b11111 == 0b10000 {
             return self.sbcd(inst);
         }
@@ -19,32 +74,100 @@ impl<'a> Cpu<'a> {
         }
     }
 
-    fn divu(&mut self, _inst: u16) {
-        todo!()
+    fn divu(&mut self, inst: u16) -> CpuResult {
+        let reg = get_reg(inst, 9);
+        let ea = AddressingMode::try_from(inst)?;
+        if let AddressingMode::AddressRegisterDirect(_) = ea {
+            return Err(Exception::Illegal);
+        }
+        trace!("DIVU.w {ea}, D{reg}");
+        let divisor = self.read_ea_word(ea)?;
+        let dividend = self.read_dr(reg);
+        if divisor == 0 {
+            self.write_ccr(SR::N, is_negative(dividend, Size::Long));
+            self.write_ccr(SR::Z, dividend >> 16 == 0);
+            self.write_ccr(SR::V, false);
+            self.write_ccr(SR::C, false);
+            self.idle(4);
+            return self.exception(Vector::DivByZero as u32);
+        }
+        self.idle(divu_cycles(dividend, divisor));
+        self.write_ccr(SR::C, false);
+        let quotient = dividend / divisor as u32;
+        if quotient > 0xFFFF {
+            self.set_div_overflow();
+            return Ok(());
+        }
+        let remainder = dividend % divisor as u32;
+        self.write_dr(reg, Size::Long, (remainder << 16) | quotient);
+        self.write_ccr(SR::N, is_negative(quotient, Size::Word));
+        self.write_ccr(SR::Z, quotient == 0);
+        self.write_ccr(SR::V, false);
+        Ok(())
+    }
+
+    fn divs(&mut self, inst: u16) -> CpuResult {
+        let reg = get_reg(inst, 9);
+        let ea = AddressingMode::try_from(inst)?;
+        if let AddressingMode::AddressRegisterDirect(_) = ea {
+            return Err(Exception::Illegal);
+        }
+        trace!("DIVS.w {ea}, D{reg}");
+        let divisor = self.read_ea_word(ea)? as i16;
+        let dividend = self.read_dr(reg) as i32;
+        if divisor == 0 {
+            self.write_ccr(SR::N, false);
+            self.write_ccr(SR::Z, true);
+            self.write_ccr(SR::V, false);
+            self.write_ccr(SR::C, false);
+            self.idle(4);
+            return self.exception(Vector::DivByZero as u32);
+        }
+        self.idle(divs_cycles(dividend, divisor));
+        self.write_ccr(SR::C, false);
+        // i64 as i32::MIN / -1 does not fit in an i32
+        let quotient = dividend as i64 / divisor as i64;
+        if quotient != quotient as i16 as i64 {
+            self.set_div_overflow();
+            return Ok(());
+        }
+        let remainder = dividend as i64 % divisor as i64;
+        let quotient = quotient as u32 & 0xFFFF;
+        self.write_dr(reg, Size::Long, ((remainder as u32) << 16) | quotient);
+        self.write_ccr(SR::N, is_negative(quotient, Size::Word));
+        self.write_ccr(SR::Z, quotient == 0);
+        self.write_ccr(SR::V, false);
+        Ok(())
     }
 
-    fn divs(&mut self, _inst: u16) {
-        todo!()
+    /// Quotient does not fit in 16 bits, the destination is left unchanged
+    fn set_div_overflow(&mut self) {
+        self.write_ccr(SR::V, true);
+        self.write_ccr(SR::N, true);
+        self.write_ccr(SR::Z, false);
     }
 
-    fn sbcd(&mut self, _inst: u16) {
-        todo!()
+    fn sbcd(&mut self, inst: u16) -> CpuResult {
+        self.bcd_op(inst, "SBCD", bcd_sub)
     }
 
-    fn or(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
+    fn or(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
         let reg = get_reg(inst, 9);
         let val1 = self.read_dr(reg);
-        let ea = AddressingMode::from(inst);
-        let val2: u32 = self.read_ea(ea, size).into();
+        let ea = AddressingMode::try_from(inst)?;
+        let val2: u32 = self.read_ea(ea, size)?.into();
         let result = val1 | val2;
         if is_bit_set(inst, 8) {
             // Set EA
             trace!("OR.{size} D{reg} {ea} ({val2:#X})");
-            self.write_ea(ea, size, Value::Long(result));
+            self.write_ea(ea, size, Value::Long(result))?;
         } else {
             // Set Dn
             trace!("OR.{size} {ea} ({val2:#X}) D{reg}");
+            if size == Size::Long {
+                self.idle_after_prefetch(if ea.is_register_or_immediate() { 4 } else { 2 })?;
+            }
             self.write_dr(reg, size, result);
         }
 
@@ -52,5 +175,68 @@ impl<'a> Cpu<'a> {
         self.write_ccr(SR::Z, result == 0);
         self.write_ccr(SR::V, false);
         self.write_ccr(SR::C, false);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{divs_cycles, divu_cycles};
+    use crate::{vm::cpu::Cpu, StatusRegister as SR, Vector};
+
+    fn setup(inst: u16, dividend: u32, divisor: u32) -> Cpu {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_long(Vector::DivByZero as u32, 0x4000);
+        cpu.mmu.write_word(0x1000, inst);
+        cpu.write_dr_long(0, divisor);
+        cpu.write_dr_long(1, dividend);
+        cpu.write_ssp(0x3000);
+        cpu.write_pc(0x1000);
+        cpu
+    }
+
+    #[test]
+    fn test_divu() {
+        let mut cpu = setup(0x82C0, 100_003, 10); // DIVU.w D0, D1
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_dr(1), 0x0003_2710);
+        assert!(!cpu.read_ccr(SR::V));
+
+        // Overflow leaves the destination untouched
+        let mut cpu = setup(0x82C0, 0x0010_0000, 1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_dr(1), 0x0010_0000);
+        assert!(cpu.read_ccr(SR::V));
+
+        let mut cpu = setup(0x82C0, 5, 0);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x4000);
+        assert_eq!(cpu.mmu.read_long(0x2FFC), 0x1002);
+    }
+
+    #[test]
+    fn test_divs() {
+        let mut cpu = setup(0x83C0, -7i32 as u32, 2); // DIVS.w D0, D1
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_dr(1), 0xFFFF_FFFD);
+        assert!(cpu.read_ccr(SR::N));
+
+        let mut cpu = setup(0x83C0, 0x8000, 1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_dr(1), 0x8000);
+        assert!(cpu.read_ccr(SR::V));
+
+        let mut cpu = setup(0x83C0, i32::MIN as u32, 0xFFFF);
+        cpu.step().unwrap();
+        assert!(cpu.read_ccr(SR::V));
+    }
+
+    #[test]
+    fn test_div_cycles() {
+        // Extremes from the 68000 manual, less the instruction fetch
+        assert_eq!(divu_cycles(0, 1), 136);
+        assert_eq!(divu_cycles(0x10000, 1), 10);
+        assert_eq!(divs_cycles(-1, 1), 156);
+        assert_eq!(divs_cycles(0x10000, 1), 16);
     }
 }
@@ -1,9 +1,12 @@
 use log::info;
 
-use crate::vm::cpu::Cpu;
+use crate::vm::{
+    cpu::Cpu,
+    exception::{CpuResult, Exception},
+};
 
-impl<'a> Cpu<'a> {
-    pub(crate) fn console_trap(&mut self) {
+impl Cpu {
+    pub(crate) fn console_trap(&mut self) -> CpuResult {
         let task = self.read_dr(0);
         match task {
             0 => self.println_string(),
@@ -17,73 +20,75 @@ impl<'a> Cpu<'a> {
             8 => self.get_time(),
             9 => self.io_halt(),
             10 => self.println_string_terminated(),
-            11 => unimplemented!("Cursor positioning"),
-            12 => unimplemented!("Key echo"),
+            11 => Err(Exception::Unimplemented),
+            12 => Err(Exception::Unimplemented),
             13 => self.println_string_terminated(),
             14 => self.print_string_terminated(),
             15 => self.print_unsigned_int(),
-            _ => unimplemented!("Task {task} unknown"),
+            _ => Err(Exception::Unimplemented),
         }
     }
-    pub(crate) fn print_string(&mut self) {
-        todo!()
+    pub(crate) fn print_string(&mut self) -> CpuResult {
+        Err(Exception::Unimplemented)
     }
 
-    pub(crate) fn println_string(&mut self) {
-        todo!()
+    pub(crate) fn println_string(&mut self) -> CpuResult {
+        Err(Exception::Unimplemented)
     }
 
-    pub(crate) fn read_string(&mut self) {
-        todo!()
+    pub(crate) fn read_string(&mut self) -> CpuResult {
+        Err(Exception::Unimplemented)
     }
 
-    pub(crate) fn display_signed_int(&mut self) {
+    pub(crate) fn display_signed_int(&mut self) -> CpuResult {
         let num = self.read_dr(1);
         info!("{num}");
+        Ok(())
     }
 
-    pub(crate) fn read_num(&mut self) {
-        todo!()
+    pub(crate) fn read_num(&mut self) -> CpuResult {
+        Err(Exception::Unimplemented)
     }
 
-    pub(crate) fn read_char(&mut self) {
-        todo!()
+    pub(crate) fn read_char(&mut self) -> CpuResult {
+        Err(Exception::Unimplemented)
     }
 
-    pub(crate) fn print_char(&mut self) {
-        todo!()
+    pub(crate) fn print_char(&mut self) -> CpuResult {
+        Err(Exception::Unimplemented)
     }
 
-    pub(crate) fn pending_char(&mut self) {
-        todo!()
+    pub(crate) fn pending_char(&mut self) -> CpuResult {
+        Err(Exception::Unimplemented)
     }
 
-    pub(crate) fn get_time(&mut self) {
-        todo!()
+    pub(crate) fn get_time(&mut self) -> CpuResult {
+        Err(Exception::Unimplemented)
     }
 
-    pub(crate) fn io_halt(&mut self) {
+    pub(crate) fn io_halt(&mut self) -> CpuResult {
         self.halt()
     }
 
-    pub(crate) fn println_string_terminated(&mut self) {
-        todo!()
+    pub(crate) fn println_string_terminated(&mut self) -> CpuResult {
+        Err(Exception::Unimplemented)
     }
 
-    pub(crate) fn print_string_terminated(&mut self) {
+    pub(crate) fn print_string_terminated(&mut self) -> CpuResult {
         let mut addr = self.read_ar(1);
         let mut byte = 0xFF;
         let mut string = vec![];
         while byte != 0x00 {
-            byte = self.mmu.read_byte(addr);
+            byte = self.read_byte(addr)?;
             string.push(byte);
             addr += 1;
         }
-        let string = String::from_utf8(string).expect("Could not read bytes");
+        let string = String::from_utf8_lossy(&string);
         info!("{string}");
+        Ok(())
     }
 
-    pub(crate) fn print_unsigned_int(&mut self) {
-        todo!()
+    pub(crate) fn print_unsigned_int(&mut self) -> CpuResult {
+        Err(Exception::Unimplemented)
     }
 }
@@ -1,35 +1,47 @@
 use log::trace;
 
 use crate::{
-    types::ConditionCode,
+    types::{AddressingMode, ConditionCode, Size},
     util::{get_bits, get_reg},
-    vm::cpu::Cpu,
+    vm::{cpu::Cpu, ea::Location, exception::CpuResult},
 };
 
-impl<'a> Cpu<'a> {
-    pub(super) fn mathq_family(&mut self, inst: u16) {
+impl Cpu {
+    pub(super) fn mathq_family(&mut self, inst: u16) -> CpuResult {
         if get_bits(inst, 6, 2) == 0b11 {
             if get_bits(inst, 3, 3) == 0b001 {
-                self.dbcc(inst);
+                self.dbcc(inst)
             } else {
-                self.scc(inst);
+                self.scc(inst)
             }
         } else if get_bits(inst, 8, 1) == 0b1 {
-            self.subq(inst);
+            self.subq(inst)
         } else {
-            self.addq(inst);
+            self.addq(inst)
         }
     }
 
-    fn scc(&mut self, _inst: u16) {
-        todo!()
+    fn scc(&mut self, inst: u16) -> CpuResult {
+        let cc = ConditionCode::from(get_bits(inst, 8, 4) as u8);
+        let ea = AddressingMode::try_from(inst)?;
+        trace!("S{cc} {ea}");
+        let loc = self.locate(ea, Size::Byte)?;
+        // The 68000 reads the operand before writing it
+        if let Location::Memory(addr) = loc {
+            self.read_byte(addr)?;
+        }
+        let set = self.test_cc(cc);
+        if set && matches!(loc, Location::DataRegister(_)) {
+            self.idle_after_prefetch(2)?;
+        }
+        self.write_location(loc, Size::Byte, if set { 0xFF } else { 0x00 })
     }
 
-    fn dbcc(&mut self, inst: u16) {
+    fn dbcc(&mut self, inst: u16) -> CpuResult {
         let cc = ConditionCode::from(get_bits(inst, 8, 3) as u8);
         let reg = get_reg(inst, 0);
         let pc = self.read_pc();
-        let displacement = self.fetch_signed_word();
+        let displacement = self.fetch_signed_word()?;
         trace!("DB{cc} D{reg}");
         if !self.test_cc(cc) {
             trace!("Cond false");
@@ -37,9 +49,14 @@ impl<'a> Cpu<'a> {
             if self.read_dr(reg) != 0xFFFFFFFF {
                 let target = (pc as i64 + displacement as i64) as u32;
                 self.write_pc(target);
+                self.idle(2);
+            } else {
+                self.idle(6);
             }
         } else {
             trace!("Cond true");
+            self.idle(4);
         }
+        Ok(())
     }
 }
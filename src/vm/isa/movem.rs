@@ -3,11 +3,14 @@ use log::trace;
 use crate::{
     types::{AddressingMode, ExtensionMode, Size},
     util::is_bit_set,
-    vm::cpu::Cpu,
+    vm::{
+        cpu::Cpu,
+        exception::{CpuResult, Exception},
+    },
 };
 
-impl<'a> Cpu<'a> {
-    pub fn movem(&mut self, inst: u16) {
+impl Cpu {
+    pub fn movem(&mut self, inst: u16) -> CpuResult {
         match (is_bit_set(inst, 6), is_bit_set(inst, 10)) {
             (false, false) => self.movem_reg_to_mem_word(inst),
             (false, true) => self.movem_mem_to_reg_word(inst),
@@ -16,15 +19,16 @@ impl<'a> Cpu<'a> {
         }
     }
 
-    fn movem_mem_to_reg_long(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        let mask = self.fetch_word();
+    fn movem_mem_to_reg_long(&mut self, inst: u16) -> CpuResult {
+        let ea = AddressingMode::try_from(inst)?;
+        let mask = self.fetch_word()?;
 
-        let start = self.get_ea(ea);
+        let start = self.get_ea(ea)?;
         let mut cur = start;
+        let program = ea.is_pc_relative();
 
         trace!("MOVEM.l {ea} => [{mask:#06X}]");
-        assert!(match ea {
+        let valid = match ea {
             AddressingMode::DataRegisterDirect(_) => false,
             AddressingMode::AddressRegisterDirect(_) => false,
             AddressingMode::AddressRegisterIndirect(_) => true,
@@ -39,12 +43,15 @@ impl<'a> Cpu<'a> {
                 ExtensionMode::PcRelativeIndex => true,
                 ExtensionMode::Immediate => false,
             },
-        });
+        };
+        if !valid {
+            return Err(Exception::Illegal);
+        }
 
         for reg in 0..8 {
             // Data
             if is_bit_set(mask, reg) {
-                let val = self.mmu.read_long(cur);
+                let val = self.read_long_in(cur, program)?;
                 self.write_dr(reg, Size::Long, val);
                 cur += 4;
             }
@@ -53,20 +60,23 @@ impl<'a> Cpu<'a> {
         for reg in 0..8 {
             // Addr
             if is_bit_set(mask, reg) {
-                let val = self.mmu.read_long(cur);
+                let val = self.read_long_in(cur, program)?;
                 self.write_ar(reg, val);
                 cur += 4;
             }
         }
+        // The 68000 reads one more word past the last register
+        self.read_word_in(cur, program)?;
+        Ok(())
     }
 
-    fn movem_reg_to_mem_long(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        let mask = self.fetch_word();
+    fn movem_reg_to_mem_long(&mut self, inst: u16) -> CpuResult {
+        let ea = AddressingMode::try_from(inst)?;
+        let mask = self.fetch_word()?;
 
         trace!("MOVEM.l [{mask:#X}] => {ea}");
 
-        assert!(match ea {
+        let valid = match ea {
             AddressingMode::DataRegisterDirect(_) => false,
             AddressingMode::AddressRegisterDirect(_) => false,
             AddressingMode::AddressRegisterIndirect(_) => true,
@@ -81,7 +91,10 @@ impl<'a> Cpu<'a> {
                 ExtensionMode::PcRelativeIndex => false,
                 ExtensionMode::Immediate => false,
             },
-        });
+        };
+        if !valid {
+            return Err(Exception::Illegal);
+        }
 
         match ea {
             AddressingMode::AddressRegisterIndirectPreDecrement(_) => {
@@ -90,7 +103,7 @@ impl<'a> Cpu<'a> {
                     // Addr
                     if is_bit_set(mask, reg) {
                         let val = self.read_ar(7 - reg);
-                        self.write_ea_long(ea, val);
+                        self.write_ea_long(ea, val)?;
                     }
                 }
                 let mask = mask >> 8;
@@ -98,18 +111,18 @@ impl<'a> Cpu<'a> {
                     // Data
                     if is_bit_set(mask, reg) {
                         let val = self.read_dr(7 - reg);
-                        self.write_ea_long(ea, val);
+                        self.write_ea_long(ea, val)?;
                     }
                 }
             }
             _ => {
                 // A7 A6 A5 A4 A3 A2 A1 A0 D7 D6 D5 D4 D3 D2 D1 D0
-                let mut addr = self.get_ea(ea);
+                let mut addr = self.get_ea(ea)?;
                 for reg in 0..8 {
                     // Data
                     if is_bit_set(mask, reg) {
                         let val = self.read_dr(reg);
-                        self.mmu.write_long(addr, val);
+                        self.write_long(addr, val)?;
                         addr += 4;
                     }
                 }
@@ -118,23 +131,25 @@ impl<'a> Cpu<'a> {
                     // Addr
                     if is_bit_set(mask, reg) {
                         let val = self.read_ar(reg);
-                        self.mmu.write_long(addr, val);
+                        self.write_long(addr, val)?;
                         addr += 4;
                     }
                 }
             }
         };
+        Ok(())
     }
 
-    pub fn movem_mem_to_reg_word(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        let mask = self.fetch_word();
+    pub fn movem_mem_to_reg_word(&mut self, inst: u16) -> CpuResult {
+        let ea = AddressingMode::try_from(inst)?;
+        let mask = self.fetch_word()?;
 
-        let start = self.get_ea(ea);
+        let start = self.get_ea(ea)?;
         let mut cur = start;
+        let program = ea.is_pc_relative();
 
         trace!("MOVEM.w {ea} => [{mask:#06X}]");
-        assert!(match ea {
+        let valid = match ea {
             AddressingMode::DataRegisterDirect(_) => false,
             AddressingMode::AddressRegisterDirect(_) => false,
             AddressingMode::AddressRegisterIndirect(_) => true,
@@ -149,12 +164,15 @@ impl<'a> Cpu<'a> {
                 ExtensionMode::PcRelativeIndex => true,
                 ExtensionMode::Immediate => false,
             },
-        });
+        };
+        if !valid {
+            return Err(Exception::Illegal);
+        }
 
         for reg in 0..8 {
             // Data
             if is_bit_set(mask, reg) {
-                let val = self.mmu.read_word(cur) as u32;
+                let val = self.read_word_in(cur, program)? as u32;
                 self.write_dr(reg, Size::Word, val);
                 cur += 2;
             }
@@ -163,20 +181,23 @@ impl<'a> Cpu<'a> {
         for reg in 0..8 {
             // Addr
             if is_bit_set(mask, reg) {
-                let val = self.mmu.read_word(cur) as u32;
+                let val = self.read_word_in(cur, program)? as u32;
                 self.write_ar(reg, val);
                 cur += 2;
             }
         }
+        // The 68000 reads one more word past the last register
+        self.read_word_in(cur, program)?;
+        Ok(())
     }
 
-    fn movem_reg_to_mem_word(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        let mask = self.fetch_word();
+    fn movem_reg_to_mem_word(&mut self, inst: u16) -> CpuResult {
+        let ea = AddressingMode::try_from(inst)?;
+        let mask = self.fetch_word()?;
 
         trace!("MOVEM.w [{mask:#X}] => {ea}");
 
-        assert!(match ea {
+        let valid = match ea {
             AddressingMode::DataRegisterDirect(_) => false,
             AddressingMode::AddressRegisterDirect(_) => false,
             AddressingMode::AddressRegisterIndirect(_) => true,
@@ -191,7 +212,10 @@ impl<'a> Cpu<'a> {
                 ExtensionMode::PcRelativeIndex => false,
                 ExtensionMode::Immediate => false,
             },
-        });
+        };
+        if !valid {
+            return Err(Exception::Illegal);
+        }
 
         match ea {
             AddressingMode::AddressRegisterIndirectPreDecrement(_) => {
@@ -200,7 +224,7 @@ impl<'a> Cpu<'a> {
                     // Addr
                     if is_bit_set(mask, reg) {
                         let val = self.read_ar(7 - reg) as u16;
-                        self.write_ea_word(ea, val);
+                        self.write_ea_word(ea, val)?;
                     }
                 }
                 let mask = mask >> 8;
@@ -208,18 +232,18 @@ impl<'a> Cpu<'a> {
                     // Data
                     if is_bit_set(mask, reg) {
                         let val = self.read_dr(7 - reg) as u16;
-                        self.write_ea_word(ea, val);
+                        self.write_ea_word(ea, val)?;
                     }
                 }
             }
             _ => {
                 // A7 A6 A5 A4 A3 A2 A1 A0 D7 D6 D5 D4 D3 D2 D1 D0
-                let mut addr = self.get_ea(ea);
+                let mut addr = self.get_ea(ea)?;
                 for reg in 0..8 {
                     // Data
                     if is_bit_set(mask, reg) {
                         let val = self.read_dr(reg) as u16;
-                        self.mmu.write_word(addr, val);
+                        self.write_word(addr, val)?;
                         addr += 2;
                     }
                 }
@@ -228,11 +252,12 @@ impl<'a> Cpu<'a> {
                     // Addr
                     if is_bit_set(mask, reg) {
                         let val = self.read_ar(reg) as u16;
-                        self.mmu.write_word(addr, val);
+                        self.write_word(addr, val)?;
                         addr += 2;
                     }
                 }
             }
         };
+        Ok(())
     }
 }
@@ -2,81 +2,126 @@ use log::trace;
 
 use crate::{
     types::{AddressingMode, Size, Value},
-    util::{get_bits, get_reg, get_size, is_bit_set, is_negative, is_overflow, SizeCoding},
-    vm::cpu::Cpu,
+    util::{bcd_add, get_bits, get_reg, get_size, is_bit_set, is_negative, SizeCoding},
+    vm::{
+        cpu::Cpu,
+        ea::address_step,
+        exception::{CpuResult, Exception},
+    },
     StatusRegister as SR,
 };
 
-impl<'a> Cpu<'a> {
-    pub(super) fn mul_family(&mut self, inst: u16) {
+impl Cpu {
+    pub(super) fn mul_family(&mut self, inst: u16) -> CpuResult {
         if inst >> 4 & 0b11111 == 0b10000 {
             return self.abcd(inst);
         }
         match get_bits(inst, 3, 6) {
             0b011000..=0b011111 => self.mulu(inst),
             0b111000..=0b111111 => self.muls(inst),
-            0b101000..=0b110001 => self.exg(inst),
+            0b101000 | 0b101001 | 0b110001 => self.exg(inst),
             _ => self.and(inst),
         }
     }
 
-    fn mulu(&mut self, _inst: u16) {
-        todo!()
+    fn mulu(&mut self, inst: u16) -> CpuResult {
+        let reg = get_reg(inst, 9);
+        let ea = AddressingMode::try_from(inst)?;
+        if let AddressingMode::AddressRegisterDirect(_) = ea {
+            return Err(Exception::Illegal);
+        }
+        trace!("MULU.w {ea} D{reg}");
+        let src = self.read_ea_word(ea)?;
+        // 2 clock cycles per set bit in the source
+        self.idle_after_prefetch(34 + 2 * src.count_ones() as u64)?;
+        let res = src as u32 * (self.read_dr(reg) & 0xFFFF);
+        self.write_dr(reg, Size::Long, res);
+        self.write_ccr(SR::N, is_negative(res, Size::Long));
+        self.write_ccr(SR::Z, res == 0);
+        self.write_ccr(SR::V, false);
+        self.write_ccr(SR::C, false);
+        Ok(())
     }
 
-    fn muls(&mut self, inst: u16) {
+    fn muls(&mut self, inst: u16) -> CpuResult {
         let reg = get_reg(inst, 9);
-        let ea = AddressingMode::from(inst);
+        let ea = AddressingMode::try_from(inst)?;
+        if let AddressingMode::AddressRegisterDirect(_) = ea {
+            return Err(Exception::Illegal);
+        }
         trace!("MULS.w {ea} D{reg}");
-        let val1 = self.read_ea_word(ea) as u32;
-        let val2 = self.read_dr(reg) & 0xFFFF;
-        let res = (val1 as i32 * val2 as i32) as u32;
-        self.write_dr(reg, Size::Long, res as u32);
-
-        self.write_ccr(SR::Z, res == 0);
+        let src = self.read_ea_word(ea)?;
+        // 2 clock cycles per 01 or 10 pair in the source with a 0 appended
+        let appended = (src as u32) << 1;
+        let pairs = (appended ^ (appended >> 1)) & 0xFFFF;
+        self.idle_after_prefetch(34 + 2 * pairs.count_ones() as u64)?;
+        let dest = self.read_dr(reg) as u16;
+        let res = (src as i16 as i32).wrapping_mul(dest as i16 as i32) as u32;
+        self.write_dr(reg, Size::Long, res);
         self.write_ccr(SR::N, is_negative(res, Size::Long));
-        self.write_ccr(SR::V, is_overflow(val1, val2, res, Size::Long));
-        self.write_ccr(SR::N, false);
+        self.write_ccr(SR::Z, res == 0);
+        self.write_ccr(SR::V, false);
+        self.write_ccr(SR::C, false);
+        Ok(())
+    }
+
+    fn abcd(&mut self, inst: u16) -> CpuResult {
+        self.bcd_op(inst, "ABCD", bcd_add)
     }
 
-    fn abcd(&mut self, inst: u16) {
+    /// Shared operand handling of ABCD and SBCD, either Dy,Dx or
+    /// -(Ay),-(Ax), with the source read first
+    pub(super) fn bcd_op(
+        &mut self,
+        inst: u16,
+        name: &str,
+        op: fn(u8, u8, bool) -> (u8, bool, bool),
+    ) -> CpuResult {
         let rx = get_reg(inst, 9);
         let ry = get_reg(inst, 0);
-        let x = self.read_ccr(SR::X) as u8;
-        let (res, carry) = if is_bit_set(inst, 3) {
-            // Addr
-            let rx = AddressingMode::AddressRegisterIndirectPreDecrement(rx);
-            let ry = AddressingMode::AddressRegisterIndirectPreDecrement(ry);
-            let vx = self.read_ea_byte(rx) as u32;
-            let vy = self.read_ea_byte(ry) as u32;
-            trace!("ABCD {ry} ({vy}), {rx} ({vx})");
-            let d1 = ((vx + vy + x as u32) % 10) as u8;
-            let d2 = ((vx + vy + x as u32) / 10) as u8;
-            let res = d1 + (d2 << 4);
-            self.write_ea_byte(rx, res);
-            (res, d2 != 0)
+        let x = self.read_ccr(SR::X);
+        if is_bit_set(inst, 3) {
+            trace!("{name} -(A{ry}), -(A{rx})");
+            let src_addr = self.resolve_ea(
+                AddressingMode::AddressRegisterIndirectPreDecrement(ry),
+                Size::Byte,
+            )?;
+            let src = self.read_byte(src_addr)?;
+            // Only one internal cycle for both decrements
+            self.decrement_ar(rx, address_step(rx, Size::Byte));
+            let dst_addr = self.read_ar(rx);
+            let dst = self.read_byte(dst_addr)?;
+            let (res, carry, overflow) = op(dst, src, x);
+            self.write_byte(dst_addr, res)?;
+            self.set_bcd_ccr(res, carry, overflow);
         } else {
-            // Data
-            let vx = self.read_dr(rx) as u8;
-            let vy = self.read_dr(ry) as u8;
-            trace!("ABCD D{ry}, D{rx}");
-            let d1 = (vx + vy + x) % 10;
-            let d2 = (vx + vy + x) / 10;
-            let res = d1 + (d2 << 4);
+            trace!("{name} D{ry}, D{rx}");
+            let src = self.read_dr(ry) as u8;
+            let dst = self.read_dr(rx) as u8;
+            let (res, carry, overflow) = op(dst, src, x);
+            self.idle_after_prefetch(2)?;
             self.write_dr_byte(rx, res);
-            (res, d2 != 0)
-        };
-        // TODO: X and C flags incorrect
+            self.set_bcd_ccr(res, carry, overflow);
+        }
+        Ok(())
+    }
+
+    /// Flags of the BCD instructions, Z is only ever cleared so that
+    /// multi-byte operations can test the whole result
+    pub(super) fn set_bcd_ccr(&mut self, res: u8, carry: bool, overflow: bool) {
         self.write_ccr(SR::X, carry);
         self.write_ccr(SR::C, carry);
+        self.write_ccr(SR::V, overflow);
+        self.write_ccr(SR::N, res & 0x80 != 0);
         if res != 0 {
             self.write_ccr(SR::Z, false);
         }
     }
 
-    fn exg(&mut self, inst: u16) {
+    fn exg(&mut self, inst: u16) -> CpuResult {
         let rx = get_reg(inst, 9);
         let ry = get_reg(inst, 0);
+        self.idle_after_prefetch(2)?;
         let mode = get_bits(inst, 3, 5);
         match mode {
             0b01000 => {
@@ -103,24 +148,28 @@ impl<'a> Cpu<'a> {
                 self.write_ar(ry, vx);
                 trace!("EXG D{rx} A{ry}");
             }
-            _ => unreachable!(),
+            _ => return Err(Exception::Illegal),
         }
+        Ok(())
     }
 
-    fn and(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
+    fn and(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
         let reg = get_reg(inst, 9);
         let val1 = self.read_dr(reg);
-        let ea = AddressingMode::from(inst);
-        let val2: u32 = self.read_ea(ea, size).into();
+        let ea = AddressingMode::try_from(inst)?;
+        let val2: u32 = self.read_ea(ea, size)?.into();
         let result = val1 & val2;
         if is_bit_set(inst, 8) {
             // Set EA
             trace!("AND.{size} D{reg} {ea} ({val2:#X})");
-            self.write_ea(ea, size, Value::Long(result));
+            self.write_ea(ea, size, Value::Long(result))?;
         } else {
             // Set Dn
             trace!("AND.{size} {ea} ({val2:#X}) D{reg}");
+            if size == Size::Long {
+                self.idle_after_prefetch(if ea.is_register_or_immediate() { 4 } else { 2 })?;
+            }
             self.write_dr(reg, size, result);
         }
 
@@ -128,5 +177,82 @@ impl<'a> Cpu<'a> {
         self.write_ccr(SR::Z, result == 0);
         self.write_ccr(SR::V, false);
         self.write_ccr(SR::C, false);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{vm::cpu::Cpu, StatusRegister as SR, Vector};
+
+    #[test]
+    fn test_muls() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0xC1C0); // MULS D0, D0
+        cpu.mmu.write_word(0x1002, 0xC3C0); // MULS D0, D1
+        cpu.write_dr_long(0, 0x1234_8000);
+        cpu.write_dr_long(1, 0x0000_0003);
+        cpu.write_sr(0x2003);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_dr(0), 0x4000_0000);
+        assert!(!cpu.read_ccr(SR::N));
+        assert!(!cpu.read_ccr(SR::V));
+        assert!(!cpu.read_ccr(SR::C));
+        cpu.write_dr_long(0, 0xFFFE);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_dr(1), 0xFFFF_FFFA);
+        assert!(cpu.read_ccr(SR::N));
+        assert!(!cpu.read_ccr(SR::Z));
+    }
+
+    #[test]
+    fn test_muls_address_register() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0xC1C8); // MULS A0, D0
+        cpu.mmu
+            .write_long(Vector::IllegalInstruction as u32, 0x4000);
+        cpu.write_ssp(0x2000);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_pc(), 0x4000);
+    }
+
+    #[test]
+    fn test_abcd_memory() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0xC308); // ABCD -(A0), -(A1)
+        cpu.mmu.write_byte(0x2000, 0x58);
+        cpu.mmu.write_byte(0x3000, 0x43);
+        cpu.write_ar(0, 0x2001);
+        cpu.write_ar(1, 0x3001);
+        cpu.write_sr(0x2014);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.mmu.read_byte(0x3000), 0x02);
+        assert_eq!(cpu.read_ar(0), 0x2000);
+        assert_eq!(cpu.read_ar(1), 0x3000);
+        assert!(cpu.read_ccr(SR::C));
+        assert!(cpu.read_ccr(SR::X));
+        assert!(!cpu.read_ccr(SR::Z));
+    }
+
+    #[test]
+    fn test_sbcd_nbcd() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0x8300); // SBCD D0, D1
+        cpu.mmu.write_word(0x1002, 0x4800); // NBCD D0
+        cpu.write_dr_long(0, 0x19);
+        cpu.write_dr_long(1, 0xFFFF_FF25);
+        cpu.write_sr(0x2004);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_dr(1), 0xFFFF_FF06);
+        assert!(!cpu.read_ccr(SR::C));
+        assert!(!cpu.read_ccr(SR::Z));
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_dr(0), 0x81);
+        assert!(cpu.read_ccr(SR::C));
+        assert!(cpu.read_ccr(SR::N));
     }
 }
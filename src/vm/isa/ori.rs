@@ -3,12 +3,12 @@ use log::{error, trace};
 use crate::{
     types::{AddressingMode, Size},
     util::{get_size, SizeCoding},
-    vm::cpu::Cpu,
-    StatusRegister as SR, Vector,
+    vm::{cpu::Cpu, exception::CpuResult},
+    StatusRegister as SR,
 };
 
-impl<'a> Cpu<'a> {
-    pub(super) fn ori_family(&mut self, inst: u16) {
+impl Cpu {
+    pub(super) fn ori_family(&mut self, inst: u16) -> CpuResult {
         if inst == 0b0000_0000_0011_1100 {
             return self.ori_to_ccr();
         }
@@ -18,40 +18,48 @@ impl<'a> Cpu<'a> {
         self.ori(inst)
     }
 
-    fn ori_to_ccr(&mut self) {
-        let val = self.fetch_word() & 0xFF;
+    fn ori_to_ccr(&mut self) -> CpuResult {
+        let val = self.fetch_word()? & 0xFF;
         let old = self.read_sr();
+        self.idle(12);
         trace!("ORI to CCR {val:#010b}");
         self.write_sr((old & 0xFF00) + ((old & 0xFF) | val));
+        Ok(())
     }
 
-    fn ori_to_sr(&mut self) {
+    fn ori_to_sr(&mut self) -> CpuResult {
         if !self.is_supervisor_mode() {
             error!("Not supervisor");
-            self.trap_vec(Vector::PrivilegeViolation as u32);
+            return self.privilege_violation();
         }
-        let val = self.fetch_word();
+        let val = self.fetch_word()?;
         let old = self.read_sr();
+        self.idle(12);
         trace!("ORI to SR {val:#018b}");
         self.write_sr(old | (val & 0b1010_0111_1111_1111));
+        Ok(())
     }
 
-    fn ori(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let ea = AddressingMode::from(inst);
-        let mut val = self.read_ea(ea, size);
+    fn ori(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let ea = AddressingMode::try_from(inst)?;
+        let mut val = self.read_ea(ea, size)?;
         let imm = match size {
-            Size::Byte => (self.fetch_word() & 0xFF) as u32,
-            Size::Word => self.fetch_word() as u32,
-            Size::Long => self.fetch_long(),
+            Size::Byte => (self.fetch_word()? & 0xFF) as u32,
+            Size::Word => self.fetch_word()? as u32,
+            Size::Long => self.fetch_long()?,
         };
         val |= imm;
-        self.write_ea(ea, size, val);
+        if size == Size::Long && ea.is_register_or_immediate() {
+            self.idle_after_prefetch(4)?;
+        }
+        self.write_ea(ea, size, val)?;
         self.write_ccr(SR::N, val.is_bit_set(-1));
         self.write_ccr(SR::Z, val == 0);
         self.write_ccr(SR::V, false);
         self.write_ccr(SR::C, false);
 
         trace!("ORI {ea:?}: {val} | {imm:#X} {size:?}");
+        Ok(())
     }
 }
@@ -1,212 +1,275 @@
+use std::fmt::Display;
+
 use log::trace;
 
 use crate::{
-    types::{AddressingMode, Size, Value},
+    types::{AddressingMode, ExtensionMode, Size},
     util::{get_bits, get_reg, get_size, is_bit_set, is_negative, SizeCoding},
-    vm::cpu::Cpu,
+    vm::{
+        cpu::Cpu,
+        exception::{CpuResult, Exception},
+    },
     StatusRegister as SR,
 };
 
-impl<'a> Cpu<'a> {
-    pub(super) fn rot_family(&mut self, inst: u16) {
-        if get_bits(inst, 6, 2) == 0b11 {
-            // Address
-            match get_bits(inst, 9, 2) {
-                0b00 => self.asd_mem(inst),
-                0b01 => self.lsd_mem(inst),
-                0b10 => self.roxd_mem(inst),
-                0b11 => self.rod_mem(inst),
-                _ => unreachable!(),
-            }
-        } else {
-            // Data Register
-            match get_bits(inst, 3, 2) {
-                0b00 => self.asd_reg(inst),
-                0b01 => self.lsd_reg(inst),
-                0b10 => self.roxd_reg(inst),
-                0b11 => self.rod_reg(inst),
-                _ => unimplemented!(),
-            }
-        }
-    }
-
-    fn asd_reg(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        trace!("ASD {ea:?}");
-        todo!()
-    }
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ShiftKind {
+    Arithmetic,
+    Logical,
+    RotateExtend,
+    Rotate,
+}
 
-    fn lsd_reg(&mut self, inst: u16) {
-        if is_bit_set(inst, 8) {
-            self.lsr_reg(inst);
-        } else {
-            self.lsl_reg(inst);
+impl From<u16> for ShiftKind {
+    fn from(value: u16) -> Self {
+        match value & 0b11 {
+            0b00 => ShiftKind::Arithmetic,
+            0b01 => ShiftKind::Logical,
+            0b10 => ShiftKind::RotateExtend,
+            0b11 => ShiftKind::Rotate,
+            _ => unreachable!(),
         }
     }
+}
 
-    fn lsr_reg(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let count = get_reg(inst, 9);
-        let a_reg = get_reg(inst, 0);
-        let shift_count = if is_bit_set(inst, 5) {
-            (self.read_dr(count) % 64) as u8
-        } else if count == 0 {
-            8u8
-        } else {
-            count
+#[derive(Debug, Copy, Clone)]
+struct Shift {
+    kind: ShiftKind,
+    left: bool,
+}
+
+impl Display for Shift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self.kind {
+            ShiftKind::Arithmetic => "AS",
+            ShiftKind::Logical => "LS",
+            ShiftKind::RotateExtend => "ROX",
+            ShiftKind::Rotate => "RO",
         };
-        trace!("LSR {count} Ar{a_reg} {size:?}");
-        let res = self.read_ar(a_reg) >> shift_count; // TODO: 24 bit mask?
-        self.write_ar(a_reg, res);
-        // TODO flags
+        write!(f, "{name}{}", if self.left { "L" } else { "R" })
     }
+}
 
-    fn lsl_reg(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let count = get_reg(inst, 9);
-        let a_reg = get_reg(inst, 0);
-        let shift_count = if is_bit_set(inst, 5) {
-            (self.read_dr(count) % 64) as u8
-        } else if count == 0 {
-            8u8
+/// Result and flags of a shift or rotate
+struct ShiftResult {
+    val: u32,
+    carry: bool,
+    overflow: bool,
+    /// New value of X, `None` when X is unaffected
+    extend: Option<bool>,
+}
+
+impl Shift {
+    /// Shift `val` of `size` by `count` one bit at a time, the way the 68000
+    /// does. A zero count clears C, except for ROXL/ROXR which copy X into C.
+    fn apply(self, val: u32, count: u32, size: Size, x: bool) -> ShiftResult {
+        let msb = 1u32 << (size.bits() - 1);
+        let mask = if size == Size::Long {
+            u32::MAX
         } else {
-            count
+            (msb << 1) - 1
         };
-        let val = self.read_ar(a_reg);
-        let res = val << shift_count; // TODO: 24 bit mask?
-        trace!("LSL {shift_count} Ar{a_reg}: {val:#X} {size:?}");
-        self.write_ar(a_reg, res);
-        // TODO flags
+        let mut val = val & mask;
+        let mut x = x;
+        let mut carry = self.kind == ShiftKind::RotateExtend && x;
+        let mut overflow = false;
+        for _ in 0..count {
+            let out = if self.left {
+                val & msb != 0
+            } else {
+                val & 1 != 0
+            };
+            let fill = match self.kind {
+                ShiftKind::Arithmetic if !self.left => val & msb != 0,
+                ShiftKind::Arithmetic | ShiftKind::Logical => false,
+                ShiftKind::RotateExtend => x,
+                ShiftKind::Rotate => out,
+            };
+            val = if self.left {
+                ((val << 1) | fill as u32) & mask
+            } else {
+                (val >> 1) | if fill { msb } else { 0 }
+            };
+            // ASL sets V if the sign bit changes at any point
+            if self.kind == ShiftKind::Arithmetic && self.left && (val & msb != 0) != out {
+                overflow = true;
+            }
+            carry = out;
+            x = out;
+        }
+        let extend = match self.kind {
+            ShiftKind::Rotate => None,
+            _ if count == 0 => None,
+            _ => Some(x),
+        };
+        ShiftResult {
+            val,
+            carry,
+            overflow,
+            extend,
+        }
     }
+}
 
-    fn roxd_reg(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        trace!("ROXD {ea:?}");
+impl Cpu {
+    pub(super) fn rot_family(&mut self, inst: u16) -> CpuResult {
+        let left = is_bit_set(inst, 8);
+        if get_bits(inst, 6, 2) == 0b11 {
+            // Memory
+            if is_bit_set(inst, 11) {
+                return Err(Exception::Illegal);
+            }
+            let kind = ShiftKind::from(get_bits(inst, 9, 2));
+            self.shift_mem(inst, Shift { kind, left })
+        } else {
+            // Data Register
+            let kind = ShiftKind::from(get_bits(inst, 3, 2));
+            self.shift_reg(inst, Shift { kind, left })
+        }
     }
 
-    fn rod_reg(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let dreg = get_reg(inst, 0);
+    /// Shift a data register by an immediate count of 1-8 or by another data
+    /// register modulo 64
+    fn shift_reg(&mut self, inst: u16, shift: Shift) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let reg = get_reg(inst, 0);
         let count = get_reg(inst, 9);
-        let shift_count = if is_bit_set(inst, 5) {
-            (self.read_dr(count) % 64) as u8
-        } else if count == 0 {
-            8u8
+        let count = if is_bit_set(inst, 5) {
+            trace!("{shift}.{size} D{count}, D{reg}");
+            self.read_dr(count) % 64
         } else {
+            let count = if count == 0 { 8 } else { count as u32 };
+            trace!("{shift}.{size} #{count}, D{reg}");
             count
         };
-        let mut val = self.read_dr_sized(dreg, size);
-        let (val, c_val) = if get_bits(inst, 8, 1) == 0 {
-            // Right
-            let c_val = (shift_count % size.bits()) - 1;
-            trace!("ROR.{size} {shift_count}, D{dreg} ({c_val})");
-            val.rotate_right(shift_count as u32);
-            (u32::from(val), is_bit_set(val, c_val))
-        } else {
-            // Left
-            let c_val = size.bits() - (shift_count % size.bits());
-            trace!("ROL.{size} {shift_count}, D{dreg} ({c_val})");
-            val.rotate_left(shift_count as u32);
-            (u32::from(val), is_bit_set(val, c_val))
-        };
-        self.write_dr(dreg, size, val);
-        self.write_ccr(SR::N, is_negative(val, size));
-        self.write_ccr(SR::Z, val == 0);
-        self.write_ccr(SR::V, false);
-        self.write_ccr(SR::C, c_val);
-        // TODO fix trace debug - distinguish imm vs reg
+        self.idle_after_prefetch(2 * count as u64 + if size == Size::Long { 4 } else { 2 })?;
+        let val = self.read_dr(reg);
+        let res = shift.apply(val, count, size, self.read_ccr(SR::X));
+        self.write_dr(reg, size, res.val);
+        self.set_shift_ccr(&res, size);
+        Ok(())
     }
 
-    fn asd_mem(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        trace!("ASD {ea:?}");
-        todo!()
+    /// Shift a memory word by one bit
+    fn shift_mem(&mut self, inst: u16, shift: Shift) -> CpuResult {
+        let ea = AddressingMode::try_from(inst)?;
+        match ea {
+            AddressingMode::DataRegisterDirect(_)
+            | AddressingMode::AddressRegisterDirect(_)
+            | AddressingMode::Extension(
+                ExtensionMode::PcRelativeDisplacement
+                | ExtensionMode::PcRelativeIndex
+                | ExtensionMode::Immediate,
+            ) => return Err(Exception::Illegal),
+            _ => {}
+        }
+        trace!("{shift}.w {ea}");
+        let addr = self.resolve_ea(ea, Size::Word)?;
+        let val = self.read_word(addr)?;
+        let res = shift.apply(val.into(), 1, Size::Word, self.read_ccr(SR::X));
+        self.write_word(addr, res.val as u16)?;
+        self.set_shift_ccr(&res, Size::Word);
+        Ok(())
     }
 
-    fn lsd_mem(&mut self, inst: u16) {
-        if is_bit_set(inst, 8) {
-            self.lsr_mem(inst);
-        } else {
-            self.lsl_mem(inst);
+    fn set_shift_ccr(&mut self, res: &ShiftResult, size: Size) {
+        if let Some(x) = res.extend {
+            self.write_ccr(SR::X, x);
         }
+        self.write_ccr(SR::N, is_negative(res.val, size));
+        self.write_ccr(SR::Z, res.val == 0);
+        self.write_ccr(SR::V, res.overflow);
+        self.write_ccr(SR::C, res.carry);
     }
+}
 
-    fn lsr_mem(&mut self, inst: u16) {
-        let size = match (inst & 0b0000_0000_1100_0000) >> 6 {
-            0b00 => Size::Byte,
-            0b01 => Size::Word,
-            0b10 => Size::Long,
-            _ => unreachable!(),
-        };
-        let ea = AddressingMode::from(inst);
-        let val = self.read_ea(ea, size);
-        trace!("LSR {ea:?}: {val}");
-        let _rot = match val {
-            Value::Byte(v) => v % 64,
-            Value::Word(v) => (v % 64) as u8,
-            Value::Long(v) => (v % 64) as u8,
-        };
-        todo!()
-    }
+#[cfg(test)]
+mod test {
+    use super::{Shift, ShiftKind};
+    use crate::types::Size;
 
-    fn lsl_mem(&mut self, inst: u16) {
-        let size = match (inst & 0b0000_0000_1100_0000) >> 6 {
-            0b00 => Size::Byte,
-            0b01 => Size::Word,
-            0b10 => Size::Long,
-            _ => unreachable!(),
-        };
-        let ea = AddressingMode::from(inst);
-        let val = self.read_ea(ea, size);
-        trace!("LSL {ea:?}: {val}");
-        let _rot = match val {
-            Value::Byte(v) => v % 64,
-            Value::Word(v) => (v % 64) as u8,
-            Value::Long(v) => (v % 64) as u8,
-        };
-        todo!()
+    fn shift(kind: ShiftKind, left: bool, val: u32, count: u32, size: Size) -> (u32, bool, bool) {
+        let res = Shift { kind, left }.apply(val, count, size, true);
+        (res.val, res.carry, res.overflow)
     }
 
-    fn roxd_mem(&mut self, inst: u16) {
-        if (inst & 0b0000_0001_0000_0000) == 0 {
-            self.roxr_mem(inst);
-        } else {
-            self.roxl_mem(inst);
-        }
+    #[test]
+    fn test_arithmetic() {
+        use ShiftKind::Arithmetic;
+        assert_eq!(
+            shift(Arithmetic, true, 0x40, 1, Size::Byte),
+            (0x80, false, true)
+        );
+        // V is kept even if the sign bit changes back
+        assert_eq!(
+            shift(Arithmetic, true, 0x60, 3, Size::Byte),
+            (0x00, true, true)
+        );
+        assert_eq!(
+            shift(Arithmetic, false, 0x8001, 1, Size::Word),
+            (0xC000, true, false)
+        );
+        assert_eq!(
+            shift(Arithmetic, false, 0x8000_0000, 40, Size::Long),
+            (u32::MAX, true, false)
+        );
     }
 
-    fn roxl_mem(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        let val = self.read_ea_word(ea);
-        trace!("ROXL {ea:?}: {val:#X}");
-        todo!()
+    #[test]
+    fn test_rotate() {
+        use ShiftKind::*;
+        assert_eq!(
+            shift(Rotate, true, 0x81, 1, Size::Byte),
+            (0x03, true, false)
+        );
+        assert_eq!(
+            shift(Rotate, false, 0x0001, 4, Size::Word),
+            (0x1000, false, false)
+        );
+        // X rotates in, so 9 bits make a full turn
+        assert_eq!(
+            shift(RotateExtend, true, 0x80, 1, Size::Byte),
+            (0x01, true, false)
+        );
+        assert_eq!(
+            shift(RotateExtend, false, 0x12, 9, Size::Byte),
+            (0x12, true, false)
+        );
     }
 
-    fn roxr_mem(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        let val = self.read_ea_word(ea);
-        trace!("ROXR {ea:?}: {val:#X}");
-        let out_bit = (0b1 & val) == 0b1;
-        let mut val = val >> 1;
-        if self.read_ccr(SR::X) {
-            val |= 0b1000_0000_0000_0000;
+    #[test]
+    fn test_zero_count() {
+        use ShiftKind::*;
+        let res = Shift {
+            kind: Logical,
+            left: true,
         }
-        self.write_ea_word(ea, val);
-
-        // SR
-        self.write_ccr(SR::C, out_bit);
-        self.write_ccr(SR::V, false);
-        self.write_ccr(SR::Z, val == 0);
-        self.write_ccr(
-            SR::N,
-            (val & 0b1000_0000_0000_0000) == 0b1000_0000_0000_0000,
-        );
-        self.write_ccr(SR::X, out_bit);
+        .apply(0x12, 0, Size::Byte, true);
+        assert!(!res.carry);
+        assert_eq!(res.extend, None);
+        let res = Shift {
+            kind: RotateExtend,
+            left: true,
+        }
+        .apply(0x12, 0, Size::Byte, true);
+        assert!(res.carry);
+        assert_eq!(res.val, 0x12);
     }
 
-    fn rod_mem(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        trace!("ROD {ea:?}");
-        todo!()
+    #[test]
+    fn test_logical() {
+        use ShiftKind::Logical;
+        assert_eq!(
+            shift(Logical, true, 0xFF, 8, Size::Byte),
+            (0x00, true, false)
+        );
+        assert_eq!(
+            shift(Logical, false, 0xFFFF_FFFF, 32, Size::Long),
+            (0, true, false)
+        );
+        assert_eq!(
+            shift(Logical, false, 0xFFFF_FFFF, 33, Size::Long),
+            (0, false, false)
+        );
     }
 }
@@ -2,16 +2,19 @@ use log::trace;
 
 use crate::{
     types::{AddressingMode, Size},
-    util::{get_bits, get_size, is_carry, is_negative, sign_extend_16_to_32, SizeCoding},
-    vm::cpu::Cpu,
+    util::{get_bits, get_size, is_negative, sign_extend_16_to_32, sub_carry_overflow, SizeCoding},
+    vm::{cpu::Cpu, ea::Location, exception::CpuResult},
     StatusRegister as SR,
 };
 
-impl<'a> Cpu<'a> {
-    pub(super) fn sub_family(&mut self, inst: u16) {
+impl Cpu {
+    pub(super) fn sub_family(&mut self, inst: u16) -> CpuResult {
         let reg = ((inst & 0b0000_1110_0000_0000) >> 9) as u8;
-        let ea = AddressingMode::from(inst);
+        let ea = AddressingMode::try_from(inst)?;
         let opmode = (inst & 0b0000_0001_1100_0000) >> 6;
+        if matches!(opmode, 0b100..=0b110) && get_bits(inst, 4, 2) == 0 {
+            return self.extend_op(inst, true);
+        }
         match opmode {
             0b000 => self.sub_data(reg, ea, Size::Byte),
             0b001 => self.sub_data(reg, ea, Size::Word),
@@ -22,117 +25,148 @@ impl<'a> Cpu<'a> {
             0b110 => self.sub_addr(reg, ea, Size::Long),
             0b111 => self.suba(reg, ea, Size::Long),
             _ => unreachable!("{inst:018b}"),
-        };
+        }
     }
 
-    fn suba(&mut self, reg: u8, ea: AddressingMode, size: Size) {
+    fn suba(&mut self, reg: u8, ea: AddressingMode, size: Size) -> CpuResult {
         trace!("SUBA.{size} A{reg} {ea:?}");
-        let val1 = self.read_ar(reg);
-        let val2 = self.read_ea(ea, size);
+        let addr = self.read_ar(reg);
+        let src = self.read_ea(ea, size)?;
+        self.idle_after_prefetch(if size == Size::Word || ea.is_register_or_immediate() {
+            4
+        } else {
+            2
+        })?;
+        // The whole register is changed and the flags are not affected
         let res = match size {
             Size::Byte => unreachable!(),
-            Size::Word => val1.wrapping_sub(sign_extend_16_to_32(u32::from(val2) as u16)),
-            Size::Long => val1.wrapping_sub(val2.into()),
+            Size::Word => addr.wrapping_sub(sign_extend_16_to_32(u32::from(src) as u16)),
+            Size::Long => addr.wrapping_sub(src.into()),
         };
         self.write_ar(reg, res);
-        sub_set_ccr(self, val1, val2.into(), res, size);
+        Ok(())
     }
 
-    fn sub_data(&mut self, reg: u8, ea: AddressingMode, size: Size) {
+    fn sub_data(&mut self, reg: u8, ea: AddressingMode, size: Size) -> CpuResult {
         trace!("SUB.{size} D{reg} {ea}");
-        let val1 = self.read_dr(reg);
-        let val2 = match size {
-            Size::Byte => self.read_ea_byte(ea) as u32,
-            Size::Word => self.read_ea_word(ea) as u32,
-            Size::Long => self.read_ea_long(ea),
+        let dst = self.read_dr(reg) & size.mask();
+        let src = match size {
+            Size::Byte => self.read_ea_byte(ea)? as u32,
+            Size::Word => self.read_ea_word(ea)? as u32,
+            Size::Long => self.read_ea_long(ea)?,
         };
-        let res = val1.wrapping_sub(val2);
-        sub_set_ccr(self, val1, val2, res, size);
+        let res = dst.wrapping_sub(src) & size.mask();
+        if size == Size::Long {
+            self.idle_after_prefetch(if ea.is_register_or_immediate() { 4 } else { 2 })?;
+        }
         self.write_dr(reg, size, res);
+        self.set_sub_ccr(dst, src, res, size);
+        Ok(())
     }
 
-    fn sub_addr(&mut self, reg: u8, ea: AddressingMode, size: Size) {
-        trace!("SUB.{size} {ea} D{reg}");
-        todo!()
+    fn sub_addr(&mut self, reg: u8, ea: AddressingMode, size: Size) -> CpuResult {
+        trace!("SUB.{size} D{reg}, {ea}");
+        let src = self.read_dr(reg) & size.mask();
+        let loc = self.locate(ea, size)?;
+        let dst = self.read_location(loc, size)?;
+        let res = dst.wrapping_sub(src) & size.mask();
+        self.write_location(loc, size, res)?;
+        self.set_sub_ccr(dst, src, res, size);
+        Ok(())
     }
 
-    pub(crate) fn subi(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let ea = AddressingMode::from(inst);
-        let val = self.read_ea(ea, size);
-        trace!("SUBI.{size} (IMM) {ea:?} ({val})");
-        todo!()
+    pub(crate) fn subi(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let imm = self.fetch_immediate(size)?;
+        let ea = AddressingMode::try_from(inst)?;
+        trace!("SUBI.{size} #{imm:#X}, {ea}");
+        let loc = self.locate(ea, size)?;
+        let dst = self.read_location(loc, size)?;
+        if size == Size::Long && matches!(loc, Location::DataRegister(_)) {
+            self.idle_after_prefetch(4)?;
+        }
+        let res = dst.wrapping_sub(imm) & size.mask();
+        self.write_location(loc, size, res)?;
+        self.set_sub_ccr(dst, imm, res, size);
+        Ok(())
     }
 
-    pub(crate) fn subq(&mut self, inst: u16) {
-        let data = get_bits(inst, 9, 3);
-        let sub = if data == 0 { 8 } else { data as u8 };
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let ea = AddressingMode::from(inst);
-        let val = self.read_ea(ea, size);
-        let res = val - sub;
-        trace!("SUBQ.{size} {sub}, {ea} ({val:X})");
-        self.write_ea(ea, size, res);
-
-        sub_set_ccr(self, val.into(), sub.into(), res.into(), size);
+    /// Flags of `res = dst - src`, including X
+    pub(super) fn set_sub_ccr(&mut self, dst: u32, src: u32, res: u32, size: Size) {
+        let (carry, overflow) = sub_carry_overflow(dst, src, res, size);
+        self.write_ccr(SR::X, carry);
+        self.write_ccr(SR::C, carry);
+        self.write_ccr(SR::N, is_negative(res, size));
+        self.write_ccr(SR::Z, res == 0);
+        self.write_ccr(SR::V, overflow);
     }
-}
 
-fn sub_set_ccr(cpu: &mut Cpu, val1: u32, val2: u32, res: u32, size: Size) {
-    cpu.write_ccr(SR::X, is_carry(val1, val2, res, size));
-    cpu.write_ccr(SR::C, is_carry(val1, val2, res, size));
-    cpu.write_ccr(SR::N, is_negative(res, size));
-    cpu.write_ccr(SR::Z, res == 0);
-    cpu.write_ccr(SR::V, sub_set_overflow(val1, val2, res, size));
-}
-
-pub fn sub_set_carry(a: u32, b: u32, res: u32, size: Size) -> bool {
-    let sa = is_negative(a, size);
-    let sb = is_negative(b, size);
-    let sc = is_negative(res, size);
-    (!sa && !sb && sc) || (sa && sb && !sc)
-}
-
-pub fn sub_set_overflow(a: u32, b: u32, res: u32, size: Size) -> bool {
-    let sa = is_negative(a, size);
-    let sb = is_negative(b, size);
-    let sc = is_negative(res, size);
-    (!sa && sb && sc) || (sa && !sb && !sc)
+    pub(crate) fn subq(&mut self, inst: u16) -> CpuResult {
+        let data = get_bits(inst, 9, 3);
+        let src = if data == 0 { 8 } else { data as u32 };
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let ea = AddressingMode::try_from(inst)?;
+        trace!("SUBQ.{size} #{src}, {ea}");
+        if let AddressingMode::AddressRegisterDirect(reg) = ea {
+            // The whole register is changed and the flags are not affected
+            self.idle_after_prefetch(4)?;
+            self.write_ar(reg, self.read_ar(reg).wrapping_sub(src));
+            return Ok(());
+        }
+        let loc = self.locate(ea, size)?;
+        let dst = self.read_location(loc, size)?;
+        if size == Size::Long && matches!(loc, Location::DataRegister(_)) {
+            self.idle_after_prefetch(4)?;
+        }
+        let res = dst.wrapping_sub(src) & size.mask();
+        self.write_location(loc, size, res)?;
+        self.set_sub_ccr(dst, src, res, size);
+        Ok(())
+    }
 }
 
 #[cfg(test)]
-mod tests {
-    use super::sub_set_carry;
+mod test {
+    use crate::{vm::cpu::Cpu, StatusRegister as SR};
+
+    #[test]
+    fn test_subq_memory() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0x5363); // SUBQ.W #1, -(A3)
+        cpu.mmu.write_word(0x2000, 0x8000);
+        cpu.write_ar(3, 0x2002);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.mmu.read_word(0x2000), 0x7FFF);
+        assert_eq!(cpu.read_ar(3), 0x2000);
+        assert!(cpu.read_ccr(SR::V));
+        assert!(!cpu.read_ccr(SR::C));
+        assert!(!cpu.read_ccr(SR::N));
+    }
 
     #[test]
-    fn test_carry() {
-        assert!(sub_set_carry(
-            0b0,
-            0b1,
-            0b1111_1111,
-            crate::types::Size::Byte
-        ));
-        assert!(!sub_set_carry(
-            0b1000_0000,
-            0b1,
-            0b0111_1111,
-            crate::types::Size::Byte
-        ));
+    fn test_subq_borrow() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0x5B01); // SUBQ.B #5, D1
+        cpu.write_dr_long(1, 0x1234_5603);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_dr(1), 0x1234_56FE);
+        assert!(cpu.read_ccr(SR::C));
+        assert!(cpu.read_ccr(SR::X));
+        assert!(cpu.read_ccr(SR::N));
+        assert!(!cpu.read_ccr(SR::V));
     }
 
     #[test]
-    fn test_overflow() {
-        assert!(sub_set_carry(
-            0b0,
-            0b1,
-            0b1111_1111,
-            crate::types::Size::Byte
-        ));
-        assert!(!sub_set_carry(
-            0b1000_0000,
-            0b1,
-            0b0111_1111,
-            crate::types::Size::Byte
-        ));
+    fn test_subq_address_register() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0x5148); // SUBQ.W #8, A0
+        cpu.write_ar(0, 0x0001_0004);
+        cpu.write_sr(0x2700);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_ar(0), 0xFFFC);
+        assert_eq!(cpu.read_sr(), 0x2700);
     }
 }
@@ -1,20 +1,21 @@
 use log::{error, trace};
 
 use crate::{
-    types::{AddressingMode, Size, Value},
+    types::{AddressingMode, ExtensionMode, Size, Value},
     util::{
-        get_reg, get_size, is_bit_set, is_negative, sign_extend_16_to_32, sign_extend_8_to_16,
-        SizeCoding,
+        bcd_sub, get_reg, get_size, is_bit_set, is_negative, sign_extend_16_to_32,
+        sign_extend_8_to_16, SizeCoding,
     },
     vm::{
         cpu::Cpu,
-        isa::sub::{sub_set_carry, sub_set_overflow},
+        ea::Location,
+        exception::{CpuResult, Exception},
     },
     StatusRegister as SR, Vector,
 };
 
-impl<'a> Cpu<'a> {
-    pub(super) fn util_family(&mut self, inst: u16) {
+impl Cpu {
+    pub(super) fn util_family(&mut self, inst: u16) -> CpuResult {
         if (inst & 0b0000_1111_1011_1000) == 0b0000_1000_1000_0000 {
             return self.ext(inst);
         }
@@ -54,133 +55,178 @@ impl<'a> Cpu<'a> {
             0b0100_1110_0111_0111 => self.rtr(),
             0b0100_1110_1000_0000..=0b0100_1110_1011_1111 => self.jsr(inst),
             0b0100_1110_1100_0000..=0b0100_1110_1111_1111 => self.jmp(inst),
-            _ => unreachable!("{inst:018b}"),
+            _ => Err(Exception::Illegal),
         }
     }
-    fn move_from_sr(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
+    fn move_from_sr(&mut self, inst: u16) -> CpuResult {
+        let ea = AddressingMode::try_from(inst)?;
         let val = self.read_sr();
-        self.write_ea_word(ea, val);
-        trace!("MOVE SR, {ea} ({val:#X})")
+        if let AddressingMode::DataRegisterDirect(_) = ea {
+            self.idle_after_prefetch(2)?;
+        }
+        self.write_ea_word(ea, val)?;
+        trace!("MOVE SR, {ea} ({val:#X})");
+        Ok(())
     }
 
-    fn move_to_ccr(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        let val = 0b0001_1111 & self.read_ea_word(ea);
+    fn move_to_ccr(&mut self, inst: u16) -> CpuResult {
+        let ea = AddressingMode::try_from(inst)?;
+        let val = 0b0001_1111 & self.read_ea_word(ea)?;
+        self.idle(8);
         trace!("MOVE {ea} ({val:#X}), CCR");
         let new = (self.read_sr() & 0xFF00) + val;
         self.write_sr(new);
+        Ok(())
     }
 
-    fn move_to_sr(&mut self, inst: u16) {
+    fn move_to_sr(&mut self, inst: u16) -> CpuResult {
         if !self.is_supervisor_mode() {
             error!("Not supervisor");
-            self.trap_vec(Vector::PrivilegeViolation as u32);
+            return self.privilege_violation();
         }
-        let ea = AddressingMode::from(inst);
-        let val = 0b1010_0111_1111_1111 & self.read_ea_word(ea);
+        let ea = AddressingMode::try_from(inst)?;
+        let val = 0b1010_0111_1111_1111 & self.read_ea_word(ea)?;
+        self.idle(8);
         trace!("MOVE {ea} ({val:#X}), SR");
         self.write_sr(val);
+        Ok(())
     }
 
-    fn illegal(&mut self) {
+    fn illegal(&mut self) -> CpuResult {
         trace!("ILLEGAL");
-        self.trap_vec(Vector::IllegalInstruction as u32);
+        self.abort_instruction(Vector::IllegalInstruction as u32)
     }
 
-    fn tst(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let ea = AddressingMode::from(inst);
-        let val = self.read_ea(ea, size);
+    fn tst(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let ea = AddressingMode::try_from(inst)?;
+        let val = self.read_ea(ea, size)?;
         trace!("TST.{size} {ea} ({val:#X})");
         self.write_ccr(SR::N, is_negative(val, size));
         self.write_ccr(SR::Z, val == 0);
         self.write_ccr(SR::V, false);
         self.write_ccr(SR::C, false);
+        Ok(())
     }
 
-    fn tas(&mut self, _inst: u16) {
-        todo!()
+    fn tas(&mut self, inst: u16) -> CpuResult {
+        let ea = AddressingMode::try_from(inst)?;
+        trace!("TAS {ea}");
+        let val = match self.locate(ea, Size::Byte)? {
+            Location::DataRegister(reg) => {
+                let val = self.read_dr(reg) as u8;
+                self.write_dr_byte(reg, val | 0x80);
+                val
+            }
+            Location::Memory(addr) => self.test_and_set(addr)?,
+        };
+        self.write_ccr(SR::N, is_negative(val, Size::Byte));
+        self.write_ccr(SR::Z, val == 0);
+        self.write_ccr(SR::V, false);
+        self.write_ccr(SR::C, false);
+        Ok(())
     }
 
-    fn trap(&mut self, inst: u16) {
+    fn trap(&mut self, inst: u16) -> CpuResult {
         let vec = inst as u32 & 0b1111;
         error!("TRAP {vec}");
         if vec == 15 {
             return self.console_trap();
         }
-        self.trap_vec(vec * 4 + Vector::Trap as u32);
+        self.exception(vec * 4 + Vector::Trap as u32)
     }
 
-    fn link(&mut self, inst: u16) {
+    fn link(&mut self, inst: u16) -> CpuResult {
         let reg = get_reg(inst, 0);
         let val = self.read_ar(reg);
-        let displacement = self.fetch_signed_word();
-        self.push_long(val);
+        let displacement = self.fetch_signed_word()?;
+        self.push_long(val)?;
         let new_sp = (self.read_sp() as i64 + displacement as i64) as u32;
         self.write_ar(reg, self.read_sp());
         self.write_sp(new_sp);
         trace!("LINK {reg} {displacement}");
+        Ok(())
     }
 
-    fn unlk(&mut self, inst: u16) {
+    fn unlk(&mut self, inst: u16) -> CpuResult {
         let reg = get_reg(inst, 0);
         trace!("UNLK A{reg}");
         self.write_sp(self.read_ar(reg));
-        let new = self.pop_long();
+        let new = self.pop_long()?;
         self.write_ar(reg, new);
+        Ok(())
     }
 
-    fn reset(&mut self) {
+    fn reset(&mut self) -> CpuResult {
         if !self.is_supervisor_mode() {
             error!("Not supervisor");
-            self.trap_vec(Vector::PrivilegeViolation as u32);
+            return self.privilege_violation();
         }
         trace!("RESET");
+        self.mmu.reset_devices();
+        self.idle(128);
+        Ok(())
     }
 
-    fn nop(&mut self) {}
+    fn nop(&mut self) -> CpuResult {
+        Ok(())
+    }
 
-    fn stop(&mut self) {
+    fn stop(&mut self) -> CpuResult {
         if !self.is_supervisor_mode() {
             error!("Not supervisor");
-            self.trap_vec(Vector::PrivilegeViolation as u32);
+            return self.privilege_violation();
         }
-        todo!()
+        let sr = self.fetch_word()?;
+        trace!("STOP #{sr:#X}");
+        self.write_sr(sr & 0b1010_0111_0001_1111);
+        self.stopped = true;
+        Ok(())
     }
 
-    fn rte(&mut self) {
+    fn rte(&mut self) -> CpuResult {
         if !self.is_supervisor_mode() {
             error!("Not supervisor");
-            self.trap_vec(Vector::PrivilegeViolation as u32);
+            return self.privilege_violation();
         }
-        let sr = self.pop_word();
-        self.write_sr(sr);
-        let pc = self.pop_long();
+        // Both are popped from the supervisor stack before SR can leave
+        // supervisor mode
+        let sr = self.pop_word()?;
+        let pc = self.pop_long()?;
+        self.write_sr(sr & 0b1010_0111_0001_1111);
         self.write_pc(pc & 0xFFFFFF);
         trace!("RTE");
+        Ok(())
     }
 
-    fn rts(&mut self) {
-        let pc = self.pop_long();
+    fn rts(&mut self) -> CpuResult {
+        let pc = self.pop_long()?;
         trace!("{} RTS", self.read_pc());
         self.write_pc(pc);
+        Ok(())
     }
 
-    fn trapv(&mut self) {
+    fn trapv(&mut self) -> CpuResult {
+        trace!("TRAPV");
         if self.read_ccr(SR::V) {
-            self.trap(7)
+            self.exception(Vector::TrapV as u32)?;
         }
+        Ok(())
     }
 
-    fn rtr(&mut self) {
-        todo!()
+    fn rtr(&mut self) -> CpuResult {
+        trace!("RTR");
+        let ccr = self.pop_word()?;
+        self.write_sr((self.read_sr() & 0xFF00) | (ccr & 0b0001_1111));
+        let pc = self.pop_long()?;
+        self.write_pc(pc);
+        Ok(())
     }
 
-    fn move_usp(&mut self, inst: u16) {
+    fn move_usp(&mut self, inst: u16) -> CpuResult {
         if !self.is_supervisor_mode() {
             error!("Not supervisor");
-            self.trap_vec(Vector::PrivilegeViolation as u32);
+            return self.privilege_violation();
         }
         let reg = get_reg(inst, 0);
         if is_bit_set(inst, 3) {
@@ -191,75 +237,90 @@ impl<'a> Cpu<'a> {
             self.write_usp(self.read_ar(reg));
         }
         trace!("MOVE USP A{reg}");
+        Ok(())
     }
 
-    fn lea(&mut self, inst: u16) {
+    fn lea(&mut self, inst: u16) -> CpuResult {
         let reg = get_reg(inst, 9);
-        let ea = AddressingMode::from(inst);
-        let val = self.get_ea(ea);
+        let ea = AddressingMode::try_from(inst)?;
+        let val = self.get_ea(ea)?;
+        self.idle(Self::address_calc_idle(ea));
         trace!("LEA A{reg} {ea} ({val:#010X})");
         self.write_ar(reg, val);
+        Ok(())
     }
 
-    fn clr(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let ea = AddressingMode::from(inst);
+    fn clr(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let ea = AddressingMode::try_from(inst)?;
         let val = match size {
             Size::Byte => Value::Byte(0),
             Size::Word => Value::Word(0),
             Size::Long => Value::Long(0),
         };
-        self.write_ea(ea, size, val);
+        match (ea, size) {
+            (AddressingMode::DataRegisterDirect(_), Size::Long) => self.idle_after_prefetch(2)?,
+            (AddressingMode::DataRegisterDirect(_), _) => {}
+            // Time of the read the 68000 makes before clearing the operand
+            (_, Size::Long) => self.idle(8),
+            (_, _) => self.idle(4),
+        }
+        self.write_ea(ea, size, val)?;
         trace!("CLR.{size} {ea}");
         self.write_ccr(SR::N, false);
         self.write_ccr(SR::Z, true);
         self.write_ccr(SR::V, false);
         self.write_ccr(SR::C, false);
+        Ok(())
     }
 
-    fn negx(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let ea = AddressingMode::from(inst);
-        let val = self.read_ea(ea, size);
+    fn negx(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let ea = AddressingMode::try_from(inst)?;
+        let val = self.read_ea(ea, size)?;
+        if let (AddressingMode::DataRegisterDirect(_), Size::Long) = (ea, size) {
+            self.idle_after_prefetch(2)?;
+        }
         trace!("NEG.{size} {ea} ({val:#X})");
         let res = 0u32.wrapping_sub(u32::from(val) + self.read_ccr(SR::X) as u32);
-        self.write_ea(ea, size, Value::Long(res));
+        self.write_ea(ea, size, Value::Long(res))?;
 
-        self.write_ccr(SR::X, sub_set_carry(0, val.into(), res, size));
-        self.write_ccr(SR::N, is_negative(res, size));
-        self.write_ccr(SR::Z, res == 0);
-        self.write_ccr(SR::V, sub_set_overflow(0, val.into(), res, size));
-        self.write_ccr(SR::C, sub_set_carry(0, val.into(), res, size));
+        self.set_sub_ccr(0, val.into(), res & size.mask(), size);
+        Ok(())
     }
 
-    fn neg(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let ea = AddressingMode::from(inst);
-        let val = self.read_ea(ea, size);
+    fn neg(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let ea = AddressingMode::try_from(inst)?;
+        let val = self.read_ea(ea, size)?;
+        if let (AddressingMode::DataRegisterDirect(_), Size::Long) = (ea, size) {
+            self.idle_after_prefetch(2)?;
+        }
         trace!("NEG.{size} {ea} ({val:#X})");
         let res = 0u32.wrapping_sub(val.into());
 
-        self.write_ccr(SR::X, sub_set_carry(0, val.into(), res, size));
-        self.write_ccr(SR::N, is_negative(res, size));
-        self.write_ccr(SR::Z, res == 0);
-        self.write_ccr(SR::V, sub_set_overflow(0, val.into(), res, size));
-        self.write_ccr(SR::C, sub_set_carry(0, val.into(), res, size));
+        self.set_sub_ccr(0, val.into(), res & size.mask(), size);
+        Ok(())
     }
 
-    fn not(&mut self, inst: u16) {
-        let size = get_size(inst, 6, SizeCoding::Pink);
-        let ea = AddressingMode::from(inst);
-        let val = self.read_ea(ea, size);
+    fn not(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 6, SizeCoding::Pink)?;
+        let ea = AddressingMode::try_from(inst)?;
+        let val = self.read_ea(ea, size)?;
+        if let (AddressingMode::DataRegisterDirect(_), Size::Long) = (ea, size) {
+            self.idle_after_prefetch(2)?;
+        }
         let res: u32 = !(u32::from(val));
-        self.write_ea(ea, size, Value::Long(res));
+        self.write_ea(ea, size, Value::Long(res))?;
         trace!("NOT.{size} {ea} ({val})");
         self.write_ccr(SR::N, is_negative(res, size));
         self.write_ccr(SR::Z, res == 0);
         self.write_ccr(SR::V, is_negative(res, size) == is_negative(val, size));
         self.write_ccr(SR::C, is_negative(res, size) == is_negative(val, size));
+        Ok(())
     }
 
-    fn ext(&mut self, inst: u16) {
+    fn ext(&mut self, inst: u16) -> CpuResult {
         let reg = get_reg(inst, 0);
         let val = self.read_dr(reg);
         let (res, size) = if is_bit_set(inst, 6) {
@@ -275,13 +336,38 @@ impl<'a> Cpu<'a> {
         self.write_ccr(SR::Z, res == 0);
         self.write_ccr(SR::V, false);
         self.write_ccr(SR::C, false);
+        Ok(())
     }
 
-    fn nbcd(&mut self, _inst: u16) {
-        todo!()
+    fn nbcd(&mut self, inst: u16) -> CpuResult {
+        let ea = AddressingMode::try_from(inst)?;
+        trace!("NBCD {ea}");
+        let x = self.read_ccr(SR::X);
+        let (res, carry, overflow) = match ea {
+            AddressingMode::DataRegisterDirect(reg) => {
+                let (res, carry, overflow) = bcd_sub(0, self.read_dr(reg) as u8, x);
+                self.idle_after_prefetch(2)?;
+                self.write_dr_byte(reg, res);
+                (res, carry, overflow)
+            }
+            AddressingMode::AddressRegisterDirect(_)
+            | AddressingMode::Extension(ExtensionMode::PcRelativeDisplacement)
+            | AddressingMode::Extension(ExtensionMode::PcRelativeIndex)
+            | AddressingMode::Extension(ExtensionMode::Immediate) => {
+                return Err(Exception::Illegal)
+            }
+            _ => {
+                let addr = self.resolve_ea(ea, Size::Byte)?;
+                let (res, carry, overflow) = bcd_sub(0, self.read_byte(addr)?, x);
+                self.write_byte(addr, res)?;
+                (res, carry, overflow)
+            }
+        };
+        self.set_bcd_ccr(res, carry, overflow);
+        Ok(())
     }
 
-    fn swap(&mut self, inst: u16) {
+    fn swap(&mut self, inst: u16) -> CpuResult {
         let reg = get_reg(inst, 0);
         let val = self.read_dr(reg);
         let high = val >> 16;
@@ -289,48 +375,130 @@ impl<'a> Cpu<'a> {
         let new = (low << 16) + high;
         trace!("SWAP D{reg}");
         self.write_dr(reg, Size::Long, new);
+        Ok(())
     }
 
-    fn pea(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        let val = self.get_ea(ea);
+    fn pea(&mut self, inst: u16) -> CpuResult {
+        let ea = AddressingMode::try_from(inst)?;
+        let val = self.get_ea(ea)?;
+        self.idle(Self::address_calc_idle(ea));
         trace!("PEA {ea} ({val:#X})");
-        self.push_long(val);
+        self.push_long(val)
     }
 
-    fn chk(&mut self, inst: u16) {
-        let size = get_size(inst, 7, SizeCoding::Purple);
+    fn chk(&mut self, inst: u16) -> CpuResult {
+        let size = get_size(inst, 7, SizeCoding::Purple)?;
         let reg = get_reg(inst, 9);
-        let val1 = self.read_dr(reg);
-        let ea = AddressingMode::from(inst);
-        let val2 = u32::from(self.read_ea(ea, size)) as i32;
+        let ea = AddressingMode::try_from(inst)?;
+        let (val1, val2) = match self.read_ea(ea, size)? {
+            Value::Long(v) => (self.read_dr(reg) as i32, v as i32),
+            v => (self.read_dr(reg) as i16 as i32, u32::from(v) as i16 as i32),
+        };
         trace!("CHK.{size} {ea} ({val2:#X}) D{reg}");
-        if (val1 as i32) < 0 {
+        self.idle(6);
+        if val1 < 0 {
             self.write_ccr(SR::N, true);
-            self.trap_vec(Vector::Chk as u32);
-        }
-        if (val1 as i32) > val2 {
+            self.exception(Vector::Chk as u32)?;
+        } else if val1 > val2 {
             self.write_ccr(SR::N, false);
-            self.trap_vec(Vector::Chk as u32);
+            self.exception(Vector::Chk as u32)?;
         }
+        Ok(())
     }
 
-    fn jsr(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        let addr = self.get_ea(ea);
+    fn jsr(&mut self, inst: u16) -> CpuResult {
+        let ea = AddressingMode::try_from(inst)?;
+        let addr = self.get_ea(ea)?;
+        self.idle(Self::jump_idle(ea));
         trace!("JSR {ea} ({addr:#X})");
-        self.push_long(self.read_pc());
+        self.push_long(self.read_pc())?;
         self.write_pc(addr);
+        Ok(())
     }
 
-    fn jmp(&mut self, inst: u16) {
-        let ea = AddressingMode::from(inst);
-        let addr = self.get_ea(ea);
+    fn jmp(&mut self, inst: u16) -> CpuResult {
+        let ea = AddressingMode::try_from(inst)?;
+        let addr = self.get_ea(ea)?;
+        self.idle(Self::jump_idle(ea));
         trace!("JMP {ea} ({addr:#X})");
         self.write_pc(addr);
+        Ok(())
     }
 
-    pub(crate) fn halt(&mut self) {
+    pub(crate) fn halt(&mut self) -> CpuResult {
         self.decrement_pc(2);
+        Err(Exception::Halt)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{vm::cpu::Cpu, StatusRegister as SR};
+
+    #[test]
+    fn test_tas() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0x4AC0); // TAS D0
+        cpu.write_dr_long(0, 0x1234_5600);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_dr(0), 0x1234_5680);
+        assert!(cpu.read_ccr(SR::Z));
+        assert!(!cpu.read_ccr(SR::N));
+    }
+
+    #[test]
+    fn test_stop_mask() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_long(0x1000, 0x4E72_FFFF); // STOP #0xFFFF
+        cpu.write_sr(0x2700);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        assert!(cpu.is_stopped());
+        assert_eq!(cpu.read_sr(), 0xA71F);
+    }
+
+    #[test]
+    fn test_rte() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0x4E73); // RTE
+        cpu.mmu.write_word(0x4000, 0x4E73); // RTE
+        cpu.mmu.write_word(0x3000, 0x7FFF);
+        cpu.mmu.write_long(0x3002, 0x4000);
+        cpu.write_ssp(0x3000);
+        cpu.write_sr(0x2700);
+        cpu.write_pc(0x1000);
+        cpu.step().unwrap();
+        // The unimplemented bits are not restored
+        assert_eq!(cpu.read_sr(), 0x271F);
+        assert_eq!(cpu.read_pc(), 0x4000);
+        assert_eq!(cpu.read_ssp(), 0x3006);
+        // Returning to user mode takes the PC from the supervisor stack
+        cpu.mmu.write_word(0x3006, 0x0000);
+        cpu.mmu.write_long(0x3008, 0x5000);
+        cpu.write_usp(0x6000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_sr(), 0x0000);
+        assert_eq!(cpu.read_pc(), 0x5000);
+        assert_eq!(cpu.read_ssp(), 0x300C);
+    }
+
+    #[test]
+    fn test_rtr() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0x4E77); // RTR
+        cpu.mmu.write_word(0x3000, 0xFFFF);
+        cpu.mmu.write_long(0x3002, 0x4000);
+        cpu.write_ssp(0x3000);
+        cpu.write_sr(0x2700);
+        cpu.write_pc(0x1000);
+        cpu.fill_prefetch().unwrap();
+        let start = cpu.cycles();
+        cpu.step().unwrap();
+        assert_eq!(cpu.cycles() - start, 20);
+        // Only the CCR is restored, without the unused bits 5-7
+        assert_eq!(cpu.read_sr(), 0x271F);
+        assert_eq!(cpu.read_pc(), 0x4000);
+        assert_eq!(cpu.read_ar(7), 0x3006);
     }
 }
@@ -1,76 +1,242 @@
+use std::fmt::Debug;
+
+use super::device::{BusError, Device, Ram};
+use crate::types::Size;
+
 pub const RAM_SIZE: usize = 0x1000000;
 
-#[derive(Debug)]
-pub struct Mmu<'a> {
-    ram: &'a mut [u8],
+/// Address space selected by the FC0-FC2 lines during a bus cycle
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FunctionCode {
+    UserData = 0b001,
+    UserProgram = 0b010,
+    SupervisorData = 0b101,
+    SupervisorProgram = 0b110,
+    InterruptAck = 0b111,
+}
+
+impl FunctionCode {
+    pub fn new(supervisor: bool, program: bool) -> Self {
+        match (supervisor, program) {
+            (false, false) => Self::UserData,
+            (false, true) => Self::UserProgram,
+            (true, false) => Self::SupervisorData,
+            (true, true) => Self::SupervisorProgram,
+        }
+    }
+
+    pub fn is_supervisor(self) -> bool {
+        u8::from(self) & 0b100 != 0
+    }
+
+    pub fn is_program(self) -> bool {
+        self == Self::UserProgram || self == Self::SupervisorProgram
+    }
+}
+
+impl From<FunctionCode> for u8 {
+    fn from(value: FunctionCode) -> Self {
+        value as u8
+    }
+}
+
+struct Region {
+    start: u32,
+    end: u32,
+    device: Box<dyn Device>,
+}
+
+impl Clone for Region {
+    fn clone(&self) -> Self {
+        Self {
+            start: self.start,
+            end: self.end,
+            device: self.device.clone_device(),
+        }
+    }
+}
+
+/// The 24 bit address space seen by the CPU, made of devices mapped at
+/// address ranges. CPU accesses to unmapped addresses end in a bus error,
+/// debugger reads of them return 0xFF and loader writes are ignored.
+#[derive(Clone)]
+pub struct Mmu {
+    regions: Vec<Region>,
+    overlay: Option<u32>,
+    booting: bool,
+}
+
+impl Debug for Mmu {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.regions.iter().map(|r| r.start..r.end))
+            .finish()
+    }
 }
 
-#[allow(dead_code)]
-impl<'a> Mmu<'a> {
-    pub fn load(&mut self, buffer: &[u8]) {
-        for (i, x) in buffer.iter().enumerate() {
-            assert!(i < 0xFFFFFF, "Max address indexed");
-            self.ram[i] = *x;
+impl Mmu {
+    /// Address space with nothing mapped
+    pub fn empty() -> Self {
+        Self {
+            regions: vec![],
+            overlay: None,
+            booting: false,
         }
     }
 
     pub fn from_vec(buffer: Vec<u8>) -> Self {
-        Mmu {
-            ram: Box::leak(buffer.into_boxed_slice()),
+        let mut mmu = Self::empty();
+        let len = buffer.len().next_multiple_of(2);
+        let mut buffer = buffer;
+        buffer.resize(len, 0xFF);
+        mmu.map(0, len as u32, Ram::from_vec(buffer));
+        mmu
+    }
+
+    /// Map `device` at `start..start + len`. Regions must be word aligned,
+    /// lie within the 24 bit address space, not overlap and be no larger
+    /// than the memory behind the device.
+    pub fn map(&mut self, start: u32, len: u32, device: impl Device + 'static) {
+        assert!(
+            start.is_multiple_of(2) && len.is_multiple_of(2),
+            "Region not word aligned"
+        );
+        let end = start
+            .checked_add(len)
+            .filter(|end| *end <= RAM_SIZE as u32)
+            .expect("Region outside the address space");
+        assert!(
+            device.size().is_none_or(|size| len as usize <= size),
+            "Region larger than the device"
+        );
+        let idx = self.regions.partition_point(|r| r.end <= start);
+        if let Some(next) = self.regions.get(idx) {
+            assert!(next.start >= end, "Region overlaps {:#X}", next.start);
+        }
+        self.regions.insert(
+            idx,
+            Region {
+                start,
+                end,
+                device: Box::new(device),
+            },
+        );
+    }
+
+    /// Mirror the region mapped at `base` at address 0 while the reset
+    /// vectors are fetched, as machines with ROM at the top of memory do
+    pub fn set_overlay(&mut self, base: Option<u32>) {
+        self.overlay = base;
+    }
+
+    pub(crate) fn set_booting(&mut self, booting: bool) {
+        self.booting = booting;
+    }
+
+    fn read_address(&self, addr: u32) -> u32 {
+        let addr = addr & 0xFFFFFF;
+        match self.overlay {
+            Some(base) if self.booting => match self.region(base) {
+                Some(r) if addr < r.end - base => base + addr,
+                _ => addr,
+            },
+            _ => addr,
+        }
+    }
+
+    /// Pulse the reset line of every mapped device
+    pub fn reset_devices(&mut self) {
+        for r in &mut self.regions {
+            r.device.reset();
+        }
+    }
+
+    fn region(&self, addr: u32) -> Option<&Region> {
+        let addr = addr & 0xFFFFFF;
+        let idx = self.regions.partition_point(|r| r.end <= addr);
+        self.regions.get(idx).filter(|r| r.start <= addr)
+    }
+
+    fn region_mut(&mut self, addr: u32) -> Option<&mut Region> {
+        let addr = addr & 0xFFFFFF;
+        let idx = self.regions.partition_point(|r| r.end <= addr);
+        self.regions.get_mut(idx).filter(|r| r.start <= addr)
+    }
+
+    /// Store `data` from `addr` on behalf of a loader. Fails with the first
+    /// address that is past the 24 bit address space or has nothing mapped.
+    pub fn load(&mut self, addr: u32, data: &[u8]) -> Result<(), u32> {
+        for (i, byte) in data.iter().enumerate() {
+            let at = addr.wrapping_add(i as u32);
+            if at < addr || at >= RAM_SIZE as u32 || self.region(at).is_none() {
+                return Err(at);
+            }
+            self.write_byte(at, *byte);
         }
+        Ok(())
     }
 
+    /// Byte at `addr` without side effects, as seen by a debugger
     pub fn read_byte(&self, addr: u32) -> u8 {
-        let addr = addr as usize & 0xFFFFFF;
-        self.ram[addr]
+        let addr = self.read_address(addr);
+        match self.region(addr) {
+            Some(r) => r.device.peek((addr & 0xFFFFFF) - r.start),
+            None => 0xFF,
+        }
     }
 
+    /// Store a byte at `addr` on behalf of a loader or debugger
     pub fn write_byte(&mut self, addr: u32, val: u8) {
-        let addr = addr as usize & 0xFFFFFF;
-        self.ram[addr] = val;
+        if let Some(r) = self.region_mut(addr) {
+            r.device.poke((addr & 0xFFFFFF) - r.start, val);
+        }
     }
 
     pub fn read_word(&self, addr: u32) -> u16 {
-        let addr = addr as usize & 0xFFFFFF;
-        assert!(addr % 2 == 0, "Memory access not word aligned!");
-        ((self.ram[addr] as u16) << 8) + self.ram[addr + 1] as u16
+        ((self.read_byte(addr) as u16) << 8) + self.read_byte(addr.wrapping_add(1)) as u16
     }
 
     pub fn write_word(&mut self, addr: u32, val: u16) {
-        let addr = addr & 0xFFFFFF;
-        assert!(addr % 2 == 0, "Memory access not word aligned!");
-        self.ram[addr as usize] = ((0xFF00 & val) >> 8) as u8;
-        self.ram[addr as usize + 1] = (0xFF & val) as u8;
+        self.write_byte(addr, ((0xFF00 & val) >> 8) as u8);
+        self.write_byte(addr.wrapping_add(1), (0xFF & val) as u8);
     }
 
     pub fn read_long(&self, addr: u32) -> u32 {
-        let addr = addr as usize & 0xFFFFFF;
-        assert!(addr % 2 == 0, "Memory access not word aligned!");
-        ((self.ram[addr] as u32) << 24)
-            + ((self.ram[addr + 1] as u32) << 16)
-            + ((self.ram[addr + 2] as u32) << 8)
-            + self.ram[addr + 3] as u32
+        ((self.read_word(addr) as u32) << 16) + self.read_word(addr.wrapping_add(2)) as u32
     }
 
     pub fn write_long(&mut self, addr: u32, val: u32) {
-        let addr = addr as usize & 0xFFFFFF;
-        assert!(addr % 2 == 0, "Memory access not word aligned!");
-        self.ram[addr] = ((0xFF000000 & val) >> 24) as u8;
-        self.ram[addr + 1] = ((0x00FF0000 & val) >> 16) as u8;
-        self.ram[addr + 2] = ((0x0000FF00 & val) >> 8) as u8;
-        self.ram[addr + 3] = (0xFF & val) as u8;
+        self.write_word(addr, (val >> 16) as u16);
+        self.write_word(addr.wrapping_add(2), val as u16);
     }
 
-    pub fn get_slice(&self) -> &[u8] {
-        self.ram
+    /// Byte or word read by the CPU in the address space `fc`
+    pub fn bus_read(&mut self, addr: u32, size: Size, fc: FunctionCode) -> Result<u16, BusError> {
+        let addr = self.read_address(addr);
+        let r = self.region_mut(addr).ok_or(BusError)?;
+        r.device.read((addr & 0xFFFFFF) - r.start, size, fc)
+    }
+
+    /// Byte or word write by the CPU in the address space `fc`
+    pub fn bus_write(
+        &mut self,
+        addr: u32,
+        size: Size,
+        val: u16,
+        fc: FunctionCode,
+    ) -> Result<(), BusError> {
+        let r = self.region_mut(addr).ok_or(BusError)?;
+        r.device.write((addr & 0xFFFFFF) - r.start, size, val, fc)
     }
 }
 
-impl<'a> Default for Mmu<'a> {
+/// `RAM_SIZE` bytes of RAM covering the whole address space
+impl Default for Mmu {
     fn default() -> Self {
-        let rambox = vec![0xFF; RAM_SIZE].into_boxed_slice();
-        let ramref = Box::leak(rambox);
-        Self { ram: ramref }
+        let mut mmu = Self::empty();
+        mmu.map(0, RAM_SIZE as u32, Ram::new(RAM_SIZE));
+        mmu
     }
 }
 
@@ -79,11 +245,23 @@ mod test {
     use super::*;
     #[test]
     fn test_memory_bounds() {
-        let mmu = Mmu::default();
-        assert_eq!(mmu.ram[0x0000], 0);
-        assert_eq!(mmu.ram[0xFFFE], 0);
-        mmu.ram[0xDEAD] = 0xAF;
-        assert_eq!(mmu.ram[0xDEAD], 0xAF);
+        let mut mmu = Mmu::default();
+        // Uninitialised RAM reads as 0xFF
+        assert_eq!(mmu.read_byte(0x0000), 0xFF);
+        assert_eq!(mmu.read_byte(0xFF_FFFF), 0xFF);
+        mmu.write_byte(0xDEAD, 0xAF);
+        assert_eq!(mmu.read_byte(0xDEAD), 0xAF);
+    }
+
+    #[test]
+    fn test_load() {
+        let mut mmu = Mmu::empty();
+        mmu.map(0x1000, 4, Ram::new(4));
+        assert_eq!(mmu.load(0x1000, &[1, 2, 3, 4]), Ok(()));
+        assert_eq!(mmu.read_long(0x1000), 0x01020304);
+        assert_eq!(mmu.load(0x1002, &[5, 6, 7]), Err(0x1004));
+        let mut mmu = Mmu::default();
+        assert_eq!(mmu.load(0xFF_FFFF, &[0, 0]), Err(0x100_0000));
     }
 
     #[test]
@@ -105,4 +283,114 @@ mod test {
         mmu.write_word(0xABCE, 0x02F0);
         assert_eq!(mmu.read_word(0xABCE), 0x02F0);
     }
+
+    #[derive(Default, Clone)]
+    struct Latch {
+        val: u8,
+    }
+
+    impl Device for Latch {
+        fn read(&mut self, _offset: u32, _size: Size, _fc: FunctionCode) -> Result<u16, BusError> {
+            Ok((!self.val).into())
+        }
+
+        fn write(
+            &mut self,
+            _offset: u32,
+            _size: Size,
+            val: u16,
+            _fc: FunctionCode,
+        ) -> Result<(), BusError> {
+            self.val = val as u8;
+            Ok(())
+        }
+
+        fn peek(&self, _offset: u32) -> u8 {
+            self.val
+        }
+
+        fn poke(&mut self, _offset: u32, val: u8) {
+            self.val = val;
+        }
+
+        fn reset(&mut self) {
+            self.val = 0;
+        }
+    }
+
+    #[test]
+    fn test_regions() {
+        let mut mmu = Mmu::empty();
+        mmu.map(0x1000, 0x100, Ram::new(0x100));
+        mmu.map(0xFF0000, 2, Latch::default());
+        mmu.write_word(0x10FE, 0x1234);
+        assert_eq!(mmu.read_word(0x10FE), 0x1234);
+        // Unmapped space
+        mmu.write_word(0x1100, 0x1234);
+        assert_eq!(mmu.read_word(0x1100), 0xFFFF);
+        assert_eq!(
+            mmu.bus_read(0x0FFE, Size::Word, FunctionCode::UserData),
+            Err(BusError)
+        );
+
+        mmu.bus_write(0xFF0001, Size::Byte, 0x0F, FunctionCode::SupervisorData)
+            .unwrap();
+        assert_eq!(mmu.read_byte(0xFF0000), 0x0F);
+        assert_eq!(
+            mmu.bus_read(0xFF0000, Size::Byte, FunctionCode::UserData),
+            Ok(0xF0)
+        );
+        // Addresses wrap at 24 bits
+        assert_eq!(mmu.read_byte(0xFFFF0000), 0x0F);
+    }
+
+    #[test]
+    #[should_panic(expected = "Region outside the address space")]
+    fn test_wrapping_region() {
+        let mut mmu = Mmu::empty();
+        mmu.map(0xFFFF_FF00, 0x200, Ram::new(0x200));
+    }
+
+    #[test]
+    #[should_panic(expected = "Region larger than the device")]
+    fn test_region_larger_than_device() {
+        let mut mmu = Mmu::empty();
+        mmu.map(0x1000, 0x200, Ram::new(0x100));
+    }
+
+    #[test]
+    #[should_panic(expected = "Region overlaps")]
+    fn test_overlap() {
+        let mut mmu = Mmu::empty();
+        mmu.map(0x1000, 0x100, Ram::new(0x100));
+        mmu.map(0x0F00, 0x200, Ram::new(0x200));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut vm = crate::VM::new();
+        vm.cpu.mmu.write_word(0x1000, 0x7001); // MOVEQ #1, D0
+        vm.set_pc(0x1000);
+        let snapshot = vm.clone();
+        vm.cpu.mmu.write_word(0x1000, 0x7002); // MOVEQ #2, D0
+        let handle = std::thread::spawn(move || {
+            let mut vm = snapshot;
+            vm.step().unwrap();
+            vm.read_dr()[0]
+        });
+        assert_eq!(handle.join().unwrap(), 1);
+        vm.step().unwrap();
+        assert_eq!(vm.read_dr()[0], 2);
+    }
+
+    #[test]
+    fn test_reset_devices() {
+        let mut vm = crate::VM::new();
+        vm.cpu.mmu = Mmu::from_vec(vec![0; 0x2000]);
+        vm.cpu.mmu.map(0xFF0000, 2, Latch { val: 0x12 });
+        vm.cpu.mmu.write_word(0x1000, 0x4E70); // RESET
+        vm.set_pc(0x1000);
+        vm.step().unwrap();
+        assert_eq!(vm.cpu.mmu.read_byte(0xFF0000), 0);
+    }
 }
@@ -1,40 +1,131 @@
 mod cpu;
 
+use std::collections::HashSet;
+
 use cpu::Cpu;
 
+use crate::{Image, LoadError, SymbolTable};
+
 pub use self::cpu::StatusRegister;
 mod ea;
+mod exception;
+pub use exception::{StepError, StopReason};
+mod interrupt;
+pub use interrupt::InterruptAck;
+mod device;
 mod isa;
+pub use device::{BusError, CloneDevice, Device, Ram, Rom};
 mod mmu;
+pub use mmu::{FunctionCode, Mmu};
+mod recorder;
+pub use recorder::{AccessKind, Transaction};
 
-#[derive(Debug, Default)]
-pub struct VM<'a> {
-    pub cpu: Cpu<'a>,
+#[derive(Debug, Default, Clone)]
+pub struct VM {
+    pub cpu: Cpu,
     pub mem_cursor: usize,
-    pub inst_time: u128,
+    /// Clock cycles taken by the last step
+    pub inst_cycles: u64,
+    pub breakpoints: HashSet<u32>,
+    /// Symbols of the loaded images
+    pub symbols: SymbolTable,
 }
 
-impl<'a> VM<'a> {
+impl VM {
     pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// VM whose address space is made of the devices mapped in `mmu`
+    pub fn with_mmu(mmu: Mmu) -> Self {
         Self {
-            inst_time: 1,
-            ..Default::default()
+            cpu: Cpu::with_mmu(mmu),
+            mem_cursor: Default::default(),
+            inst_cycles: Default::default(),
+            breakpoints: Default::default(),
+            symbols: Default::default(),
+        }
+    }
+
+    /// Hardware reset: pulse the reset line of every device, then boot from
+    /// the reset vectors at addresses 0 and 4
+    pub fn reset(&mut self) -> Result<(), StepError> {
+        self.cpu.mmu.reset_devices();
+        self.cpu.reset_exception()
+    }
+
+    pub fn load(&mut self, rom: &[u8]) -> Result<(), LoadError> {
+        self.cpu
+            .load(rom)
+            .map_err(|addr| LoadError::Unmapped { addr })
+    }
+
+    /// Place the segments of `image` in memory, add its symbols and start at
+    /// its entry point and stack, if it has them. Fails if a segment lies
+    /// outside the mapped memory.
+    pub fn load_image(&mut self, image: &Image) -> Result<(), LoadError> {
+        for segment in &image.segments {
+            self.cpu
+                .mmu
+                .load(segment.addr, &segment.data)
+                .map_err(|addr| LoadError::Unmapped { addr })?;
         }
+        if let Some(entry) = image.entry {
+            self.cpu.write_pc(entry);
+        }
+        if let Some(stack) = image.stack {
+            self.cpu.write_sp(stack);
+        }
+        self.symbols.extend(&image.symbols);
+        Ok(())
+    }
+
+    /// Run until the CPU stops or reaches a breakpoint. A breakpoint on the
+    /// current PC is ignored so that `run` can resume from it.
+    pub fn run(&mut self) -> StepError {
+        if let Err(e) = self.cpu.step() {
+            return e;
+        }
+        loop {
+            let pc = self.read_pc();
+            if self.breakpoints.contains(&pc) {
+                return StepError {
+                    reason: StopReason::Breakpoint,
+                    pc,
+                    opcode: self.cpu.peep_word(),
+                };
+            }
+            if let Err(e) = self.cpu.step() {
+                return e;
+            }
+        }
+    }
+
+    pub fn step(&mut self) -> Result<(), StepError> {
+        let start = self.cpu.cycles();
+        let res = self.cpu.step();
+        self.inst_cycles = self.cpu.cycles() - start;
+        res
+    }
+
+    /// Whether the CPU executed STOP and is waiting for an interrupt
+    pub fn is_stopped(&self) -> bool {
+        self.cpu.is_stopped()
     }
 
-    pub fn load(&mut self, rom: &[u8]) {
-        self.cpu.load(rom);
+    /// Clock cycles elapsed since power on
+    pub fn cycles(&self) -> u64 {
+        self.cpu.cycles()
     }
 
-    pub fn run(&mut self) {
-        self.cpu.run();
+    /// Request an interrupt at `level`, acknowledged with `ack`
+    pub fn raise_interrupt(&mut self, level: u8, ack: InterruptAck) {
+        self.cpu.set_ipl(level, ack);
     }
 
-    pub fn step(&mut self) {
-        let start = std::time::Instant::now();
-        self.cpu.step();
-        let now = std::time::Instant::now();
-        self.inst_time = (now - start).as_nanos();
+    /// Remove any pending interrupt request
+    pub fn lower_interrupt(&mut self) {
+        self.cpu.set_ipl(0, InterruptAck::Autovector);
     }
 
     pub fn set_pc(&mut self, pc: u32) {
@@ -0,0 +1,226 @@
+use std::fmt::Display;
+
+use super::cpu::Cpu;
+use crate::types::Size;
+
+/// Kind of bus cycle
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    /// Indivisible read-modify-write cycle of TAS
+    Tas,
+}
+
+/// Bus activity in the order it happened
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Transaction {
+    Access {
+        kind: AccessKind,
+        cycles: u64,
+        function_code: u8,
+        address: u32,
+        size: Size,
+        /// Value on the active half of the data bus, for TAS the value written
+        value: u16,
+    },
+    /// Bus idle for the given number of clock cycles
+    Idle(u64),
+}
+
+/// Formatted as in the SingleStepTests `transactions` list, for example
+/// `["r",4,6,3076,".w",10786]` or `["n",122]`
+impl Display for Transaction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Access {
+                kind,
+                cycles,
+                function_code,
+                address,
+                size,
+                value,
+            } => {
+                let kind = match kind {
+                    AccessKind::Read => "r",
+                    AccessKind::Write => "w",
+                    AccessKind::Tas => "t",
+                };
+                let size = match size {
+                    Size::Byte => ".b",
+                    _ => ".w",
+                };
+                write!(
+                    f,
+                    "[\"{kind}\",{cycles},{function_code},{address},\"{size}\",{value}]"
+                )
+            }
+            Self::Idle(cycles) => write!(f, "[\"n\",{cycles}]"),
+        }
+    }
+}
+
+impl Cpu {
+    /// Start or stop logging bus transactions, discarding any logged so far
+    pub fn record_transactions(&mut self, enable: bool) {
+        self.recorder = enable.then(Vec::new);
+    }
+
+    /// Transactions logged since recording started or the last call
+    pub fn take_transactions(&mut self) -> Vec<Transaction> {
+        self.recorder
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Log a transaction if recording, merging consecutive idle periods
+    pub(super) fn record(&mut self, transaction: Transaction) {
+        let Some(log) = self.recorder.as_mut() else {
+            return;
+        };
+        match (log.last_mut(), transaction) {
+            (Some(Transaction::Idle(total)), Transaction::Idle(cycles)) => *total += cycles,
+            _ => log.push(transaction),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AccessKind, Transaction};
+    use crate::{types::Size, vm::cpu::Cpu};
+
+    fn log(cpu: &mut Cpu) -> Vec<String> {
+        cpu.take_transactions()
+            .iter()
+            .map(|t| t.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_format() {
+        let read = Transaction::Access {
+            kind: AccessKind::Read,
+            cycles: 4,
+            function_code: 6,
+            address: 3076,
+            size: Size::Word,
+            value: 10786,
+        };
+        assert_eq!(read.to_string(), r#"["r",4,6,3076,".w",10786]"#);
+        assert_eq!(Transaction::Idle(122).to_string(), r#"["n",122]"#);
+    }
+
+    #[test]
+    fn test_record() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0x2010); // MOVE.l (A0), D0
+        cpu.mmu.write_long(0x2000, 0x1234_5678);
+        cpu.write_ar(0, 0x2000);
+        cpu.write_pc(0x1000);
+        cpu.fill_prefetch().unwrap();
+        cpu.record_transactions(true);
+        cpu.step().unwrap();
+        let log = log(&mut cpu);
+        assert_eq!(
+            log,
+            [
+                r#"["r",4,5,8192,".w",4660]"#,
+                r#"["r",4,5,8194,".w",22136]"#,
+                r#"["r",4,6,4100,".w",65535]"#,
+            ]
+        );
+        assert!(cpu.take_transactions().is_empty());
+    }
+
+    #[test]
+    fn test_predecrement_order() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0x2100); // MOVE.l D0, -(A0)
+        cpu.write_dr_long(0, 0x1234_5678);
+        cpu.write_ar(0, 0x2004);
+        cpu.write_pc(0x1000);
+        cpu.fill_prefetch().unwrap();
+        cpu.record_transactions(true);
+        cpu.step().unwrap();
+        let log = log(&mut cpu);
+        assert_eq!(
+            log,
+            [
+                r#"["w",4,5,8194,".w",22136]"#,
+                r#"["w",4,5,8192,".w",4660]"#,
+                r#"["r",4,6,4100,".w",65535]"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tas() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0x4AD0); // TAS (A0)
+        cpu.mmu.write_byte(0x2000, 0x05);
+        cpu.write_ar(0, 0x2000);
+        cpu.write_pc(0x1000);
+        cpu.fill_prefetch().unwrap();
+        cpu.record_transactions(true);
+        cpu.step().unwrap();
+        let log = log(&mut cpu);
+        assert_eq!(
+            log,
+            [r#"["t",10,5,8192,".b",5]"#, r#"["r",4,6,4100,".w",65535]"#,]
+        );
+        assert_eq!(cpu.mmu.read_byte(0x2000), 0x85);
+    }
+
+    #[test]
+    fn test_function_codes() {
+        let mut cpu = Cpu::default();
+        cpu.mmu.write_word(0x1000, 0x303A); // MOVE.w (d16,PC), D0
+        cpu.mmu.write_word(0x1002, 0x0FFE);
+        cpu.mmu.write_word(0x1004, 0x3010); // MOVE.w (A0), D0
+        cpu.write_ar(0, 0x3000);
+        cpu.write_pc(0x1000);
+        cpu.fill_prefetch().unwrap();
+        cpu.record_transactions(true);
+        cpu.step().unwrap();
+        cpu.write_sr(0x0000);
+        cpu.step().unwrap();
+        let codes: Vec<u8> = cpu
+            .take_transactions()
+            .iter()
+            .filter_map(|t| match t {
+                Transaction::Access { function_code, .. } => Some(*function_code),
+                Transaction::Idle(_) => None,
+            })
+            .collect();
+        // PC relative operands come from program space, user mode from the
+        // user spaces
+        assert_eq!(codes, [6, 6, 6, 1, 2]);
+    }
+
+    #[test]
+    fn test_prefetch_before_idle() {
+        // "e3ae [LSL.l D1, D6] 5" from the SingleStepTests README
+        let mut cpu = Cpu::default();
+        cpu.write_dr_long(1, 123414203);
+        cpu.write_dr_long(6, 2480544920);
+        cpu.write_sr(9994);
+        cpu.write_pc(3072);
+        cpu.set_prefetch_queue([58286, 50941]);
+        cpu.mmu.write_byte(3076, 42);
+        cpu.mmu.write_byte(3077, 34);
+        cpu.record_transactions(true);
+        let start = cpu.cycles();
+        cpu.step().unwrap();
+        assert_eq!(cpu.read_dr(6), 0);
+        assert_eq!(cpu.read_sr(), 9988);
+        assert_eq!(cpu.read_pc(), 3074);
+        assert_eq!(cpu.prefetch_queue(), [50941, 10786]);
+        assert_eq!(cpu.cycles() - start, 126);
+        assert_eq!(
+            log(&mut cpu),
+            [r#"["r",4,6,3076,".w",10786]"#, r#"["n",122]"#]
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use phoenix::{Format, Image, StopReason, VM};
+
+/// Run a test ROM loaded at address 0 until it halts
+fn run_rom(path: &str) -> VM {
+    let rom = std::fs::read(path).unwrap();
+    let mut vm = VM::new();
+    vm.load(&rom).unwrap();
+    vm.set_pc(0);
+    let stop = vm.run();
+    assert_eq!(stop.reason, StopReason::Halted, "{path}: {stop}");
+    vm
+}
+
+#[test]
+fn test_bit2() {
+    let vm = run_rom("roms/bit2.bin");
+    assert_eq!(
+        &vm.read_dr()[..4],
+        [0xFAFAF009, 0xA8A2B200, 0xCABAADBF, 0x0ADBDAF5]
+    );
+}
+
+#[test]
+fn test_complexbits() {
+    let vm = run_rom("roms/complexbits.bin");
+    assert_eq!(
+        &vm.read_dr()[..4],
+        [0xF009F55E, 0xBABABAA8, 0xCABAAFBF, 0x0ADB25F5]
+    );
+}
+
+#[test]
+fn test_srecord_entry() {
+    let text = "S1092000720570094E4F49\nS9032000DC\n";
+    let image = Image::parse(Format::SRecord, text.as_bytes()).unwrap();
+    let mut vm = VM::new();
+    vm.load_image(&image).unwrap();
+    assert_eq!(vm.read_pc(), 0x2000);
+    assert_eq!(vm.run().reason, StopReason::Halted);
+    assert_eq!(vm.read_dr()[1], 5);
+}
+
+#[test]
+fn test_tos_prg() {
+    let image = Image::from_file("c_progs/demo").unwrap();
+    let mut vm = VM::new();
+    vm.load_image(&image).unwrap();
+    assert_eq!(vm.read_pc(), 0x1100);
+    // vbcc startup code, from branching over its banner up to rounding the
+    // end of .bss read from the basepage
+    for _ in 0..8 {
+        vm.step().unwrap();
+    }
+    assert_eq!(vm.cpu.read_ar(6), 0x1000);
+    assert_eq!(vm.cpu.read_ar(4), 0x1100 + 0x8548);
+    assert_eq!(vm.read_dr()[7], 0x1100 + 0x548 + 0x1C + 0x1C);
+}
@@ -2,13 +2,15 @@ use std::{fs::File, path::Path};
 
 use phoenix::VM;
 use serde::Deserialize;
-use serde_json::Error;
+use serde_json::{Error, Value};
 
 #[derive(Deserialize)]
 struct TestCase {
     name: String,
     initial: State,
     r#final: State,
+    length: u64,
+    transactions: Vec<Value>,
 }
 
 #[derive(Deserialize)]
@@ -32,7 +34,7 @@ struct State {
     ssp: u32,
     sr: u32,
     pc: u32,
-    // prefetch: Vec<u32>,
+    prefetch: [u16; 2],
     ram: Vec<(u32, u8)>,
 }
 
@@ -43,13 +45,31 @@ fn read_json(file: &Path) -> Result<Vec<TestCase>, Error> {
 
 fn run_case(test: TestCase) {
     let mut vm = VM::new();
-    let (inst, _) = test.name.split_at(4);
-    init_vm(&mut vm, test.initial, inst);
-    vm.step();
+    init_vm(&mut vm, test.initial);
+    vm.cpu.record_transactions(true);
+    if let Err(e) = vm.step() {
+        panic!("{}: {e}", test.name);
+    }
     check_vm_state(&vm, test.r#final);
+    assert_eq!(vm.inst_cycles, test.length, "{}: cycles", test.name);
+    let transactions = vm.cpu.take_transactions();
+    assert_eq!(
+        transactions.len(),
+        test.transactions.len(),
+        "{}: transaction count",
+        test.name
+    );
+    for (i, (got, expected)) in transactions.iter().zip(&test.transactions).enumerate() {
+        assert_eq!(
+            got.to_string(),
+            expected.to_string(),
+            "{}: transaction {i}",
+            test.name
+        );
+    }
 }
 
-fn init_vm(vm: &mut VM, state: State, inst: &str) {
+fn init_vm(vm: &mut VM, state: State) {
     vm.cpu.write_ar(0, state.a0);
     vm.cpu.write_ar(1, state.a1);
     vm.cpu.write_ar(2, state.a2);
@@ -72,11 +92,7 @@ fn init_vm(vm: &mut VM, state: State, inst: &str) {
     for (addr, val) in state.ram {
         vm.cpu.mmu.write_byte(addr, val);
     }
-    let (a, b) = inst.split_at(2);
-    let a = u8::from_str_radix(a, 16).unwrap();
-    let b = u8::from_str_radix(b, 16).unwrap();
-    vm.cpu.mmu.write_byte(state.pc, a);
-    vm.cpu.mmu.write_byte(state.pc + 1, b);
+    vm.cpu.set_prefetch_queue(state.prefetch);
 }
 
 fn check_vm_state(vm: &VM, state: State) {
@@ -99,6 +115,7 @@ fn check_vm_state(vm: &VM, state: State) {
     assert_eq!(vm.cpu.read_ssp(), state.ssp);
     assert_eq!(vm.cpu.read_sr(), state.sr as u16);
     assert_eq!(vm.cpu.read_pc(), state.pc);
+    assert_eq!(vm.cpu.prefetch_queue(), state.prefetch);
     for (addr, val) in state.ram {
         assert_eq!(val, vm.cpu.mmu.read_byte(addr));
     }
@@ -108,7 +125,7 @@ fn test_instruction(path: &Path) -> datatest_stable::Result<()> {
     println!("Starting {:?}", &path);
     let op = read_json(path)?;
     let mut runcount = 0;
-    for test in op.into_iter().take(1) {
+    for test in op {
         run_case(test);
         runcount += 1;
     }
@@ -116,4 +133,4 @@ fn test_instruction(path: &Path) -> datatest_stable::Result<()> {
     Ok(())
 }
 
-datatest_stable::harness!(test_instruction, "tests/json", r"MOVE.*\.json");
+datatest_stable::harness!(test_instruction, "tests/json", r"\.json$");
@@ -14,7 +14,6 @@ use ratatui::{
 use simplelog::{ConfigBuilder, WriteLogger};
 use std::{
     cmp::max,
-    fs,
     io::{stdout, Result, Stdout, Write},
     sync::{Arc, Mutex},
 };
@@ -70,9 +69,28 @@ fn main() -> Result<()> {
     let _ = WriteLogger::init(log::LevelFilter::Trace, conf, log.clone());
     info!("Starting VM");
     let mut vm = VM::new();
-    if let Ok(pc_addr) = u32::from_str_radix(&args.program_counter, 16) {
-        vm.set_pc(pc_addr);
-        info!("PC set to {pc_addr:#X}");
+    info!("Loading program");
+    let images = args
+        .images()
+        .unwrap_or_else(|e| panic!("Could not load: {e}"));
+    for image in &images {
+        vm.load_image(image)
+            .unwrap_or_else(|e| panic!("Could not load: {e}"));
+        info!("{} bytes loaded to RAM", image.size());
+    }
+    match args.program_counter {
+        Some(pc) => {
+            let pc_addr = u32::from_str_radix(&pc, 16).expect("Could not parse PC");
+            vm.set_pc(pc_addr);
+            info!("PC set to {pc_addr:#X}");
+        }
+        None if images.iter().any(|image| image.entry.is_some()) => {
+            info!("PC set to entry point {:#X}", vm.read_pc())
+        }
+        None => match vm.reset() {
+            Ok(()) => info!("Booted with PC {:#X}", vm.read_pc()),
+            Err(e) => info!("Reset failed: {e}"),
+        },
     }
     if let Some(usp) = args.user_stack_pointer {
         let usp = u32::from_str_radix(&usp, 16).expect("Could not parse USP");
@@ -84,11 +102,6 @@ fn main() -> Result<()> {
         vm.set_sp(ssp);
         info!("SSP set to {ssp:#X}");
     }
-    info!("Loading program");
-    let rom = fs::read(&args.file)
-        .unwrap_or_else(|_| panic!("Could not open provided file {}", args.file));
-    vm.load(&rom);
-    info!("{} bytes loaded to RAM", rom.len());
 
     loop {
         terminal.draw(|frame| {
@@ -129,39 +142,42 @@ fn main() -> Result<()> {
             frame.render_widget(create_reg_widget(&vm), reg_block.inner(sub_right[0]));
 
             frame.render_widget(
-                Memview::new(vm.cpu.mmu.get_slice(), vm.read_pc() as usize),
+                Memview::new(&vm.cpu.mmu, &vm.symbols, vm.read_pc() as usize),
                 inst_block.inner(memory_layout[0]),
             );
 
             frame.render_widget(
-                Stackview::new(vm.cpu.mmu.get_slice(), vm.cpu.read_ar(7) as usize),
+                Stackview::new(&vm.cpu.mmu, vm.cpu.read_ar(7) as usize),
                 stack_block.inner(memory_layout[1]),
             );
         })?;
 
         match mode {
             Mode::Step => {
-                vm.step();
+                if let Err(e) = vm.step() {
+                    info!("Stopped: {e}");
+                }
                 mode = Mode::Paused;
             }
-            Mode::Running => vm.step(),
+            Mode::Running => {
+                if let Err(e) = vm.step() {
+                    info!("Stopped: {e}");
+                    mode = Mode::Paused;
+                }
+            }
             Mode::SetPC => {}
             Mode::Paused => {}
         }
 
         if event::poll(std::time::Duration::from_millis(16))? {
             match event::read()? {
-                Event::Key(key) => {
-                    if key.kind == KeyEventKind::Press {
-                        match key.code {
-                            KeyCode::Char('q') => break,
-                            KeyCode::Char('p') => mode = Mode::SetPC,
-                            KeyCode::Char(' ') => mode = Mode::Step,
-                            KeyCode::Enter => mode = Mode::Running,
-                            _ => {}
-                        }
-                    }
-                }
+                Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Char('q') => break,
+                    KeyCode::Char('p') => mode = Mode::SetPC,
+                    KeyCode::Char(' ') => mode = Mode::Step,
+                    KeyCode::Enter => mode = Mode::Running,
+                    _ => {}
+                },
                 Event::FocusLost => mode = Mode::Paused,
                 _ => {}
             }
@@ -265,7 +281,7 @@ fn create_reg_widget(vm: &VM) -> impl Widget {
             ]),
             Row::new(vec![
                 "".to_string(),
-                "".to_string(),
+                if vm.is_stopped() { "STOPPED" } else { "" }.to_string(),
                 "".to_string(),
                 "T S  INT   XNZVC".to_string(),
             ]),
@@ -276,10 +292,10 @@ fn create_reg_widget(vm: &VM) -> impl Widget {
                 format!("{:016b}", vm.cpu.read_sr()),
             ]),
             Row::new(vec![
-                "Time".to_string(),
-                format!("{} ns", vm.inst_time).to_string(),
-                "Freq".to_string(),
-                format!("{} MHz", 1000000 / vm.inst_time).to_string(),
+                "Cycles".to_string(),
+                format!("{}", vm.cycles()),
+                "Last".to_string(),
+                format!("{}", vm.inst_cycles),
             ]),
         ],
         vec![
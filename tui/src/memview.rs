@@ -1,16 +1,18 @@
+use phoenix::{Mmu, SymbolTable};
 use ratatui::{
     prelude::*,
     widgets::{Paragraph, Widget},
 };
 
 pub struct Memview<'a> {
-    ram: &'a [u8],
+    mmu: &'a Mmu,
+    symbols: &'a SymbolTable,
     pc: usize,
 }
 
 impl<'a> Memview<'a> {
-    pub fn new(ram: &'a [u8], pc: usize) -> Self {
-        Self { ram, pc }
+    pub fn new(mmu: &'a Mmu, symbols: &'a SymbolTable, pc: usize) -> Self {
+        Self { mmu, symbols, pc }
     }
 }
 
@@ -19,7 +21,7 @@ impl<'a> Widget for Memview<'a> {
     where
         Self: Sized,
     {
-        let height = if area.height % 2 == 0 {
+        let height = if area.height.is_multiple_of(2) {
             area.height
         } else {
             area.height - 1
@@ -28,17 +30,19 @@ impl<'a> Widget for Memview<'a> {
         let start_idx = self.pc.saturating_sub(height as usize);
         let end_idx = start_idx + (area.height * 2) as usize;
         for i in (start_idx..=end_idx).step_by(2) {
-            string += &format_line(self.ram, i, i == self.pc);
+            string += &format_line(self.mmu, self.symbols, i, i == self.pc);
         }
 
         Paragraph::new(string).render(area, buf);
     }
 }
 
-fn format_line(ram: &[u8], idx: usize, highlighted: bool) -> String {
+fn format_line(mmu: &Mmu, symbols: &SymbolTable, idx: usize, highlighted: bool) -> String {
+    let (hi, lo) = (mmu.read_byte(idx as u32), mmu.read_byte(idx as u32 + 1));
+    let label = symbols.name(idx as u32).unwrap_or_default();
     if highlighted {
-        format!("=>{idx:#08X}: {:#04X}{:02X}\n", ram[idx], ram[idx + 1])
+        format!("=>{idx:#08X}: {hi:#04X}{lo:02X} {label}\n")
     } else {
-        format!("  {idx:#08X}: {:#04X}{:02X}\n", ram[idx], ram[idx + 1])
+        format!("  {idx:#08X}: {hi:#04X}{lo:02X} {label}\n")
     }
 }
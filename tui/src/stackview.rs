@@ -1,16 +1,17 @@
+use phoenix::Mmu;
 use ratatui::{
     prelude::*,
     widgets::{Paragraph, Widget},
 };
 
 pub struct Stackview<'a> {
-    ram: &'a [u8],
+    mmu: &'a Mmu,
     sp: usize,
 }
 
 impl<'a> Stackview<'a> {
-    pub fn new(ram: &'a [u8], sp: usize) -> Self {
-        Self { ram, sp }
+    pub fn new(mmu: &'a Mmu, sp: usize) -> Self {
+        Self { mmu, sp }
     }
 }
 
@@ -24,29 +25,30 @@ impl<'a> Widget for Stackview<'a> {
         let start_idx = self.sp.wrapping_sub((height as usize * 4) / 2);
         let end_idx = self.sp.wrapping_add((height as usize * 4) / 2);
         for i in (start_idx..=end_idx).step_by(4) {
-            string += &format_line(self.ram, i & 0xFFFFFF, self.sp);
+            string += &format_line(self.mmu, i & 0xFFFFFF, self.sp);
         }
 
         Paragraph::new(string).render(area, buf);
     }
 }
 
-fn format_line(ram: &[u8], addr: usize, sp: usize) -> String {
+fn format_line(mmu: &Mmu, addr: usize, sp: usize) -> String {
+    let ram = |addr: usize| mmu.read_byte(addr as u32);
     if addr == (sp & 0xFFFFFF) || addr == ((sp + 2) & 0xFFFFFF) {
         format!(
             "=>{addr:#08X}: {:02X} {:02X} {:02X} {:02X}\n",
-            ram[addr],
-            ram[addr + 1],
-            ram[(addr + 2) & 0xFFFFFF],
-            ram[(addr + 3) & 0xFFFFFF]
+            ram(addr),
+            ram(addr + 1),
+            ram(addr + 2),
+            ram(addr + 3)
         )
     } else {
         format!(
             "  {addr:#08X}: {:02X} {:02X} {:02X} {:02X}\n",
-            ram[addr],
-            ram[addr + 1],
-            ram[(addr + 2) & 0xFFFFFF],
-            ram[(addr + 3) & 0xFFFFFF]
+            ram(addr),
+            ram(addr + 1),
+            ram(addr + 2),
+            ram(addr + 3)
         )
     }
 }